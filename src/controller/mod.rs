@@ -1,54 +1,215 @@
 use std::sync::{LazyLock, Mutex};
-use log::{info, warn};
+use log::{debug, info, warn};
 use rand::{random_range};
 use crate::datamodel::board::Board;
 use crate::datamodel::chess_move::ChessMove;
 use crate::datamodel::options::Options;
-use crate::move_provider::endgame::Endgame;
-use crate::move_provider::minmax::min_max::MinMax;
-use crate::move_provider::MoveProvider;
-use crate::move_provider::negamax::negamax::Negamax;
-use crate::move_provider::openings::Openings;
-
-static MOVE_PROVIDERS: LazyLock<Mutex<[Box<dyn MoveProvider + Sync + Send>; 4]>> =
-    LazyLock::new(|| Mutex::new([
-        Box::new(Openings::new()),
-        Box::new(Endgame),
-        Box::new(Negamax),
-        Box::new(MinMax),
-]));
+use crate::controller::pipeline::Pipeline;
+use crate::move_provider::scored_move::ScoredMove;
+
+pub mod pipeline;
+pub mod provider_registry;
+
+/// The move providers that are asked for a move, as configured by the options
+static PIPELINE: LazyLock<Mutex<Pipeline>> = LazyLock::new(|| Mutex::new(Pipeline::new()));
+
+/// The notifications for the move providers since the last move. The pipeline is locked during
+/// the whole search, so the notifications of the gui are passed on when the next move is requested.
+static PENDING_EVENTS: Mutex<Vec<ProviderEvent>> = Mutex::new(Vec::new());
+
+enum ProviderEvent {
+    NewGame,
+    PositionChanged(Board),
+}
 
 pub struct Controller;
 
 
 
 impl Controller {
-    pub fn give_move(board: &Board, options: &Options) -> Option<ChessMove> {
+    /// Returns the best move for the given position and the expected answer of the opponent,
+    /// if the move provider knows it.
+    pub fn give_move(board: &Board, options: &Options) -> Option<(ChessMove, Option<ChessMove>)> {
 
         info!("Move requested");
 
-        for move_provider in MOVE_PROVIDERS.lock().unwrap().iter_mut() {
+        let mut pipeline = PIPELINE.lock().unwrap();
+        pipeline.configure(options);
+
+        let events: Vec<ProviderEvent> = PENDING_EVENTS.lock().unwrap().drain(..).collect();
+        debug!("Passing [{}] notifications on to the move providers", events.len());
+        for event in events {
+            for move_provider in pipeline.providers() {
+                match &event {
+                    ProviderEvent::NewGame => move_provider.new_game(),
+                    ProviderEvent::PositionChanged(board) => move_provider.position_changed(board),
+                }
+            }
+        }
+
+        for move_provider in pipeline.providers_for(board) {
             let move_provider = move_provider.as_mut();
             info!("Requesting moves from: [{move_provider:?}]");
 
-            // get the moves from the move provider in descending order of rating (1st = best)
-            let moves: Vec<ChessMove> = move_provider.get_recommended_moves(board, options.clone());
+            // get the scored moves from the move provider, best move first
+            let moves: Vec<ScoredMove> = move_provider.get_recommended_moves(board, options.clone());
+            for scored_move in &moves {
+                debug!("Candidate [{:?}] by [{}] with score [{:.3}] and confidence [{:.2}]",
+                    scored_move.chess_move, scored_move.source, scored_move.score, scored_move.confidence);
+            }
 
-            if !moves.is_empty() {
-                let best_move = moves.get(
-                    // raising a value between 0 and 1 to a high power returns a value closer to 0
-                    // thus, a higher difficulty will yield values closer to the beginning, which are
-                    // the better moves.
-                    // the value is capped at 1 with modulo operator (for negative difficulty)
-                    (random_range(0.0..1.0f64).powi(options.difficulty().value()) % 1.0f64 * moves.len() as f64).floor() as usize
-                );
+            if let Some(best_move) = Self::select_move(&moves, options) {
+                let ponder_move = best_move.principal_variation.first().copied()
+                    .or_else(|| move_provider.get_ponder_move(board, &best_move.chess_move));
 
-                info!("Best move is [{best_move:?}] by [{move_provider:?}]");
-                return Some(best_move.unwrap().clone());
+                info!("Best move is [{:?}] by [{}] with score [{:.3}] and confidence [{:.2}], ponder move is [{ponder_move:?}]",
+                    best_move.chess_move, best_move.source, best_move.score, best_move.confidence);
+                return Some((best_move.chess_move, ponder_move));
             }
         }
 
     warn!("No possible moves were found.");
     None
     }
-}
\ No newline at end of file
+
+    /// Chooses one of the scored moves by the difficulty. The higher the difficulty, the closer
+    /// the score of the chosen move is to the score of the best move.
+    fn select_move<'a>(moves: &'a [ScoredMove], options: &Options) -> Option<&'a ScoredMove> {
+        // raising a value between 0 and 1 to a high power returns a value closer to 0
+        // thus, a higher difficulty will yield scores closer to the best score.
+        // the value is capped at 1 with modulo operator (for negative difficulty)
+        let score_loss = random_range(0.0..1.0f64).powi(options.difficulty().value()) % 1.0f64;
+        Self::closest_move(moves, score_loss)
+    }
+
+    /// Returns the move whose score is closest to the score the given share of the way from the best
+    /// to the worst score. The distance to that score grows the less sure a score is, and moves with
+    /// a score of no confidence are not chosen. Moves of the same distance are chosen by their
+    /// confidence, then in the order of the move provider.
+    fn closest_move(moves: &[ScoredMove], score_loss: f64) -> Option<&ScoredMove> {
+        let candidates: Vec<&ScoredMove> = moves.iter().filter(|scored_move| scored_move.confidence > 0.0).collect();
+        if candidates.is_empty() {
+            // the move provider only guessed, so trust its order
+            return moves.first();
+        }
+        let best_score = candidates.iter().map(|scored_move| scored_move.score).max_by(f64::total_cmp)?;
+        let worst_score = candidates.iter().map(|scored_move| scored_move.score).min_by(f64::total_cmp)?;
+        let target_score = best_score - score_loss * (best_score - worst_score);
+
+        let distance = |scored_move: &ScoredMove| (scored_move.score - target_score).abs() / scored_move.confidence;
+        candidates.into_iter().min_by(|first, second| distance(first).total_cmp(&distance(second))
+            .then(second.confidence.total_cmp(&first.confidence)))
+    }
+
+    /// Tells the move providers that the gui starts a new game, once the next move is requested.
+    /// The earlier notifications belong to the previous game, so they are dropped.
+    pub fn new_game() {
+        info!("New game");
+        let mut events = PENDING_EVENTS.lock().unwrap();
+        events.clear();
+        events.push(ProviderEvent::NewGame);
+    }
+
+    /// Tells the move providers the position the gui set up for the next search, once the next move
+    /// is requested. Only the last of several positions without a search in between is passed on.
+    pub fn position_changed(board: &Board) {
+        let mut events = PENDING_EVENTS.lock().unwrap();
+        if matches!(events.last(), Some(ProviderEvent::PositionChanged(_))) {
+            events.pop();
+        }
+        events.push(ProviderEvent::PositionChanged(board.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::converter::Converter;
+    use crate::datamodel::enums::difficulty::Difficulty;
+
+    fn scored_move(uci: &str, score: f64, confidence: f64, source: &'static str) -> ScoredMove {
+        ScoredMove::new(Converter::convert_string_to_move(&uci.to_string()), score, confidence, source)
+    }
+
+    fn closest_uci(moves: &[ScoredMove], score_loss: f64) -> Option<String> {
+        Controller::closest_move(moves, score_loss)
+            .map(|scored_move| Converter::convert_move_to_string(&scored_move.chess_move))
+    }
+
+    #[test]
+    fn no_moves_select_nothing() {
+        assert!(Controller::closest_move(&[], 0.0).is_none());
+        assert!(Controller::select_move(&[], &Options::new(Difficulty::HARD, 4)).is_none());
+    }
+
+    #[test]
+    fn single_move_is_always_selected() {
+        let moves = [scored_move("e2e4", 0.3, 0.4, "Negamax")];
+        for difficulty in [Difficulty::EASY, Difficulty::NORMAL, Difficulty::HARD] {
+            let selected = Controller::select_move(&moves, &Options::new(difficulty, 4));
+            assert_eq!(selected.map(|scored_move| scored_move.score), Some(0.3));
+        }
+    }
+
+    #[test]
+    fn score_loss_moves_the_target_from_the_best_to_the_worst_score() {
+        let moves = [
+            scored_move("e2e4", 0.5, 1.0, "Negamax"),
+            scored_move("d2d4", 0.7, 1.0, "Negamax"),
+            scored_move("g1f3", 0.6, 1.0, "Negamax"),
+        ];
+        assert_eq!(closest_uci(&moves, 0.0).as_deref(), Some("d2d4"));
+        assert_eq!(closest_uci(&moves, 0.5).as_deref(), Some("g1f3"));
+        assert_eq!(closest_uci(&moves, 0.99).as_deref(), Some("e2e4"));
+    }
+
+    #[test]
+    fn ties_are_broken_by_confidence_then_by_order() {
+        let moves = [
+            scored_move("e2e4", 0.5, 0.8, "Negamax"),
+            scored_move("d2d4", 0.5, 0.8, "Negamax"),
+        ];
+        assert_eq!(closest_uci(&moves, 0.0).as_deref(), Some("e2e4"));
+
+        let moves = [
+            scored_move("e2e4", 0.5, 0.4, "Negamax"),
+            scored_move("d2d4", 0.5, 0.8, "Negamax"),
+        ];
+        assert_eq!(closest_uci(&moves, 0.0).as_deref(), Some("d2d4"));
+        assert_eq!(closest_uci(&moves, 0.7).as_deref(), Some("d2d4"));
+    }
+
+    #[test]
+    fn moves_without_confidence_are_not_selected() {
+        let moves = [
+            scored_move("a2a3", 1.0, 0.0, "Negamax"),
+            scored_move("e2e4", 0.4, 0.5, "Negamax"),
+        ];
+        for score_loss in [0.0, 0.5, 0.99] {
+            assert_eq!(closest_uci(&moves, score_loss).as_deref(), Some("e2e4"));
+        }
+
+        // without any confidence the order of the move provider decides
+        let moves = [
+            scored_move("a2a3", 0.2, 0.0, "Negamax"),
+            scored_move("e2e4", 0.9, 0.0, "Negamax"),
+        ];
+        assert_eq!(closest_uci(&moves, 0.0).as_deref(), Some("a2a3"));
+    }
+
+    #[test]
+    fn confident_book_move_beats_an_unsure_better_score() {
+        let book_move = scored_move("e2e4", 0.55, 1.0, "PolyglotBook");
+        let unsure_move = scored_move("g2g4", 0.6, 0.05, "Negamax");
+        let sure_move = scored_move("g2g4", 0.6, 1.0, "Negamax");
+
+        // the unsure move is only chosen if the target is its score exactly
+        assert_eq!(closest_uci(&[book_move.clone(), unsure_move.clone()], 0.0).as_deref(), Some("g2g4"));
+        for score_loss in [0.1, 0.25, 0.5, 0.9] {
+            assert_eq!(closest_uci(&[unsure_move.clone(), book_move.clone()], score_loss).as_deref(), Some("e2e4"));
+        }
+        // with the same confidence the better score wins up to half the way
+        assert_eq!(closest_uci(&[book_move.clone(), sure_move.clone()], 0.25).as_deref(), Some("g2g4"));
+        assert_eq!(closest_uci(&[book_move, sure_move], 0.75).as_deref(), Some("e2e4"));
+    }
+}
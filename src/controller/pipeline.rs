@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use crate::controller::provider_registry::{BoxedMoveProvider, ProviderRegistry};
+use crate::datamodel::board::Board;
+use crate::datamodel::enums::color::Color;
+use crate::datamodel::options::Options;
+
+/// The pipeline of the engine if no other is configured
+pub const DEFAULT_PIPELINE: &str = "MateSolver,PolyglotBook,Openings,SyzygyEndgame,Endgame,Negamax,MinMax";
+
+/// A move provider of the pipeline and the positions it is asked for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PipelineStage {
+    /// The name of the move provider in the registry
+    pub provider: String,
+    /// A disabled stage is never asked for moves
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    /// The stage is skipped after this number of plies of the game
+    #[serde(default)]
+    pub max_ply: Option<u32>,
+    /// The stage is skipped for positions with more pieces
+    #[serde(default)]
+    pub max_pieces: Option<u16>,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+impl PipelineStage {
+    pub fn new(provider: &str) -> PipelineStage {
+        PipelineStage {
+            provider: provider.to_string(),
+            enabled: true,
+            max_ply: None,
+            max_pieces: None,
+        }
+    }
+
+    /// Returns whether the move provider of the stage is asked for moves in the given position.
+    pub fn applies_to(&self, board: &Board) -> bool {
+        self.enabled
+            && self.max_ply.is_none_or(|max_ply| game_ply(board) <= max_ply)
+            && self.max_pieces.is_none_or(|max_pieces| board.piece_count() <= max_pieces)
+    }
+}
+
+/// The move providers that are asked for a move one after the other,
+/// until one of them recommends moves.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PipelineConfig {
+    pub stages: Vec<PipelineStage>,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        PipelineConfig::parse(DEFAULT_PIPELINE).expect("The default pipeline is valid")
+    }
+}
+
+impl PipelineConfig {
+    /// Parses the stages of the Pipeline option, separated by commas.
+    /// A stage is the name of a move provider followed by its conditions, each after a colon,
+    /// e.g. PolyglotBook:max_ply=20 or SyzygyEndgame:max_pieces=6. A stage starting with
+    /// a minus is disabled.
+    pub fn parse(pipeline: &str) -> Result<PipelineConfig, String> {
+        let mut stages = Vec::new();
+
+        for stage in pipeline.split(',').map(str::trim).filter(|stage| !stage.is_empty()) {
+            let mut parts = stage.split(':').map(str::trim);
+            let name = parts.next().unwrap_or_default();
+            let (enabled, name) = match name.strip_prefix('-') {
+                Some(name) => (false, name.trim()),
+                None => (true, name),
+            };
+
+            let mut stage = PipelineStage::new(name);
+            stage.enabled = enabled;
+            for condition in parts {
+                let (key, value) = condition.split_once('=')
+                    .ok_or_else(|| format!("the condition {} of {} has no value", condition, name))?;
+                match key.trim() {
+                    "max_ply" => stage.max_ply = Some(parse_value(name, condition, value)?),
+                    "max_pieces" => stage.max_pieces = Some(parse_value(name, condition, value)?),
+                    key => return Err(format!("the condition {} of {} is unknown", key, name)),
+                }
+            }
+            stages.push(stage);
+        }
+
+        let config = PipelineConfig { stages };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Reads the pipeline from a toml file, or from a json file if the file does not end with .toml.
+    pub fn load(path: &str) -> io::Result<PipelineConfig> {
+        let content = fs::read_to_string(path)?;
+        let is_toml = Path::new(path).extension().is_some_and(|extension| extension.eq_ignore_ascii_case("toml"));
+        let config: PipelineConfig = if is_toml {
+            toml::from_str(&content).map_err(invalid_data)?
+        } else {
+            serde_json::from_str(&content).map_err(invalid_data)?
+        };
+        config.validate().map_err(invalid_data)?;
+        Ok(config)
+    }
+
+    /// Returns the pipeline of the options. The PipelineFile option takes precedence over
+    /// the Pipeline option. An invalid pipeline is reported and replaced by the default pipeline.
+    pub fn from_options(options: &Options) -> PipelineConfig {
+        let result = if !options.pipeline_file().is_empty() {
+            PipelineConfig::load(options.pipeline_file())
+                .map_err(|error| format!("the pipeline file {} could not be loaded: {}", options.pipeline_file(), error))
+        } else {
+            PipelineConfig::parse(options.pipeline())
+                .map_err(|error| format!("the pipeline {} is invalid: {}", options.pipeline(), error))
+        };
+
+        match result {
+            Ok(config) => config,
+            Err(error) => {
+                warn!("Using the default pipeline, as {}", error);
+                println!("info string {}", error);
+                PipelineConfig::default()
+            }
+        }
+    }
+
+    /// Checks that every stage names a registered move provider and that an enabled stage
+    /// without conditions is left, which is asked in every position.
+    fn validate(&self) -> Result<(), String> {
+        let names = ProviderRegistry::names();
+        if let Some(stage) = self.stages.iter().find(|stage| !names.contains(&stage.provider)) {
+            return Err(format!("the move provider {} is unknown, known are {}", stage.provider, names.join(", ")));
+        }
+        if !self.stages.iter().any(|stage| stage.enabled && stage.max_ply.is_none() && stage.max_pieces.is_none()) {
+            return Err("no enabled move provider is asked in every position".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The move providers of the configured pipeline.
+#[derive(Debug)]
+pub struct Pipeline {
+    /// The Pipeline and PipelineFile options the pipeline was built from
+    source: Option<(String, String)>,
+    config: Option<PipelineConfig>,
+    stages: Vec<(PipelineStage, BoxedMoveProvider)>,
+}
+
+impl Pipeline {
+    pub fn new() -> Pipeline {
+        Pipeline {
+            source: None,
+            config: None,
+            stages: Vec::new(),
+        }
+    }
+
+    /// Builds the pipeline of the options, if it was changed since the last move.
+    /// Move providers that stay part of the pipeline are kept, so they keep their state.
+    pub fn configure(&mut self, options: &Options) {
+        let source = (options.pipeline().to_string(), options.pipeline_file().to_string());
+        if self.source.as_ref() == Some(&source) {
+            return;
+        }
+        self.source = Some(source);
+
+        let config = PipelineConfig::from_options(options);
+        if self.config.as_ref() == Some(&config) {
+            return;
+        }
+
+        let mut previous_providers: HashMap<String, Vec<BoxedMoveProvider>> = HashMap::new();
+        for (stage, provider) in self.stages.drain(..) {
+            previous_providers.entry(stage.provider).or_default().push(provider);
+        }
+
+        for stage in &config.stages {
+            let provider = previous_providers.get_mut(&stage.provider)
+                .and_then(|providers| providers.pop())
+                .or_else(|| ProviderRegistry::create(&stage.provider));
+            if let Some(provider) = provider {
+                self.stages.push((stage.clone(), provider));
+            }
+        }
+
+        info!("Configured move provider pipeline: {:?}", config.stages);
+        self.config = Some(config);
+    }
+
+    /// Returns the move providers that are asked for moves in the given position, in their order.
+    pub fn providers_for<'a>(&'a mut self, board: &'a Board) -> impl Iterator<Item = &'a mut BoxedMoveProvider> + 'a {
+        self.stages.iter_mut()
+            .filter(move |(stage, _)| stage.applies_to(board))
+            .map(|(_, provider)| provider)
+    }
+
+    /// Returns all move providers of the pipeline, including those of disabled stages.
+    pub fn providers(&mut self) -> impl Iterator<Item = &mut BoxedMoveProvider> {
+        self.stages.iter_mut().map(|(_, provider)| provider)
+    }
+}
+
+/// Returns the number of plies played since the start of the game.
+fn game_ply(board: &Board) -> u32 {
+    let full_moves = (board.move_counter() as u32).saturating_sub(1);
+    full_moves * 2 + if board.next_color() == Color::BLACK { 1 } else { 0 }
+}
+
+fn parse_value<T: std::str::FromStr>(name: &str, condition: &str, value: &str) -> Result<T, String> {
+    value.trim().parse().map_err(|_| format!("the condition {} of {} has no valid number", condition, name))
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::converter::Converter;
+
+    #[test]
+    fn stages_with_conditions_are_parsed() {
+        let config = PipelineConfig::parse(" PolyglotBook:max_ply=20 , -Openings, SyzygyEndgame : max_pieces = 6:max_ply=200,Negamax,").unwrap();
+        let mut book = PipelineStage::new("PolyglotBook");
+        book.max_ply = Some(20);
+        let mut openings = PipelineStage::new("Openings");
+        openings.enabled = false;
+        let mut syzygy = PipelineStage::new("SyzygyEndgame");
+        syzygy.max_pieces = Some(6);
+        syzygy.max_ply = Some(200);
+        assert_eq!(config.stages, vec![book, openings, syzygy, PipelineStage::new("Negamax")]);
+    }
+
+    #[test]
+    fn default_pipeline_is_valid() {
+        let config = PipelineConfig::default();
+        assert_eq!(config.stages.len(), DEFAULT_PIPELINE.split(',').count());
+        assert!(config.stages.iter().all(|stage| stage.enabled));
+    }
+
+    #[test]
+    fn invalid_pipelines_are_rejected() {
+        for pipeline in [
+            "",
+            "Negamax,Unknown",
+            "Negamax:max_ply",
+            "Negamax,PolyglotBook:max_ply=many",
+            "Negamax,PolyglotBook:max_ply=-1",
+            "Negamax,PolyglotBook:depth=3",
+            "-Negamax",
+            "PolyglotBook:max_ply=20,Negamax:max_pieces=32",
+        ] {
+            assert!(PipelineConfig::parse(pipeline).is_err(), "{}", pipeline);
+        }
+    }
+
+    #[test]
+    fn conditions_decide_whether_a_stage_applies() {
+        let config = PipelineConfig::parse("PolyglotBook:max_ply=2,SyzygyEndgame:max_pieces=3,-MinMax,Negamax").unwrap();
+        let [book, syzygy, min_max, negamax] = &config.stages[..] else { panic!("four stages expected") };
+
+        let start = Converter::convert_string_to_board("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string());
+        let third_ply = Converter::convert_string_to_board("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2".to_string());
+        let fourth_ply = Converter::convert_string_to_board("rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2".to_string());
+        let endgame = Converter::convert_string_to_board("8/8/4k3/8/8/3K4/4P3/8 w - - 0 60".to_string());
+
+        assert!(book.applies_to(&start) && book.applies_to(&third_ply) && !book.applies_to(&fourth_ply));
+        assert!(!syzygy.applies_to(&start) && syzygy.applies_to(&endgame));
+        assert!(!min_max.applies_to(&start));
+        assert!(negamax.applies_to(&start) && negamax.applies_to(&endgame));
+    }
+}
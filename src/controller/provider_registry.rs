@@ -0,0 +1,65 @@
+use std::collections::BTreeMap;
+use std::sync::{LazyLock, RwLock};
+use log::info;
+use crate::evaluation::configured_evaluator::ConfiguredEvaluator;
+use crate::evaluation::material_evaluator::MaterialEvaluator;
+use crate::move_provider::endgame::Endgame;
+use crate::move_provider::minmax::min_max::MinMax;
+use crate::move_provider::negamax::mate_solver::MateSolver;
+use crate::move_provider::negamax::negamax::Negamax;
+use crate::move_provider::openings::Openings;
+use crate::move_provider::polyglot_book::PolyglotBook;
+use crate::move_provider::syzygy_endgame::SyzygyEndgame;
+use crate::move_provider::MoveProvider;
+
+/// A move provider that may be shared between the threads of the engine.
+pub type BoxedMoveProvider = Box<dyn MoveProvider + Sync + Send>;
+
+/// Creates a new instance of a move provider.
+pub type ProviderFactory = fn() -> BoxedMoveProvider;
+
+static REGISTRY: LazyLock<RwLock<ProviderRegistry>> = LazyLock::new(|| RwLock::new(ProviderRegistry::with_built_in_providers()));
+
+/// Knows the move providers a pipeline may be built of by their names.
+///
+/// New move providers are registered at start up, so they can be used in the Pipeline option
+/// without changing the controller.
+pub struct ProviderRegistry {
+    factories: BTreeMap<String, ProviderFactory>,
+}
+
+impl ProviderRegistry {
+    fn with_built_in_providers() -> ProviderRegistry {
+        let mut registry = ProviderRegistry { factories: BTreeMap::new() };
+        registry.add("MateSolver", || Box::new(MateSolver::new()));
+        registry.add("PolyglotBook", || Box::new(PolyglotBook::new()));
+        registry.add("Openings", || Box::new(Openings::new()));
+        registry.add("SyzygyEndgame", || Box::new(SyzygyEndgame::new()));
+        registry.add("Endgame", || Box::new(Endgame::new()));
+        registry.add("Negamax", || Box::new(Negamax::new(ConfiguredEvaluator::new())));
+        registry.add("MinMax", || Box::new(MinMax::new(MaterialEvaluator::new())));
+        registry
+    }
+
+    fn add(&mut self, name: &str, factory: ProviderFactory) {
+        self.factories.insert(name.to_string(), factory);
+    }
+
+    /// Registers a move provider under the given name, replacing a provider of the same name.
+    // the built in providers are added directly, this is the extension point for all others
+    #[allow(dead_code)]
+    pub fn register(name: &str, factory: ProviderFactory) {
+        info!("Registered move provider [{}]", name);
+        REGISTRY.write().unwrap().add(name, factory);
+    }
+
+    /// Creates a new instance of the move provider with the given name, if it is registered.
+    pub fn create(name: &str) -> Option<BoxedMoveProvider> {
+        REGISTRY.read().unwrap().factories.get(name).map(|factory| factory())
+    }
+
+    /// Returns the names of all registered move providers in alphabetical order.
+    pub fn names() -> Vec<String> {
+        REGISTRY.read().unwrap().factories.keys().cloned().collect()
+    }
+}
@@ -134,14 +134,16 @@ impl Converter {
             // decode possible en passant
             let en_passant_string: &String = &split[3];
             if en_passant_string != "-" {
+                // the ranks of the fen string start at 1
                 let en_passant_field = Field::new(
                     File::from_string(en_passant_string.chars().nth(0).unwrap().to_string().as_str()),
-                    Rank::from_index(en_passant_string.chars().nth(1).unwrap().to_digit(10).unwrap() as usize)
+                    Rank::from_index(en_passant_string.chars().nth(1).unwrap().to_digit(10).unwrap() as usize - 1)
                 );
                 board.set_en_passant_field(Some(en_passant_field));
             }
 
             // decode half moves
+            board.set_halfmove_clock(split.get(4).and_then(|half_moves| half_moves.parse::<u16>().ok()).unwrap_or(0));
 
             // decode move number
             board.set_move_counter(split[5].parse::<u16>().unwrap())
@@ -152,6 +154,52 @@ impl Converter {
         board
     }
 
+    /// Checks that the fen string has the fields convert_string_to_board needs, so it does not panic.
+    /// Returns the reason why the fen is invalid.
+    pub fn validate_fen(fen: &str) -> Result<(), String> {
+        let fields: Vec<&str> = fen.split(' ').collect();
+        if fields.len() != 6 {
+            return Err(format!("the fen has {} instead of 6 fields", fields.len()));
+        }
+
+        let ranks: Vec<&str> = fields[0].split('/').collect();
+        if ranks.len() != 8 {
+            return Err(format!("the placement has {} instead of 8 ranks", ranks.len()));
+        }
+        for rank in &ranks {
+            let mut files = 0;
+            for c in rank.chars() {
+                match c {
+                    '1'..='8' => files += c.to_digit(10).unwrap(),
+                    'p' | 'n' | 'b' | 'r' | 'q' | 'k' | 'P' | 'N' | 'B' | 'R' | 'Q' | 'K' => files += 1,
+                    _ => return Err(format!("the placement contains the invalid character {}", c)),
+                }
+            }
+            if files != 8 {
+                return Err(format!("the rank {} has {} instead of 8 fields", rank, files));
+            }
+        }
+        if fields[0].matches('K').count() != 1 || fields[0].matches('k').count() != 1 {
+            return Err("every player needs exactly one king".to_string());
+        }
+
+        if fields[1] != "w" && fields[1] != "b" {
+            return Err(format!("the color to move {} is invalid", fields[1]));
+        }
+        if fields[2] != "-" && !fields[2].chars().all(|c| "KQkq".contains(c)) {
+            return Err(format!("the castling rights {} are invalid", fields[2]));
+        }
+        let en_passant: Vec<char> = fields[3].chars().collect();
+        let is_en_passant_field = en_passant.len() == 2 && ('a'..='h').contains(&en_passant[0]) && (en_passant[1] == '3' || en_passant[1] == '6');
+        if fields[3] != "-" && !is_en_passant_field {
+            return Err(format!("the en passant field {} is invalid", fields[3]));
+        }
+        if fields[4].parse::<u16>().is_err() || fields[5].parse::<u16>().is_err() {
+            return Err(format!("the move counters {} {} are invalid", fields[4], fields[5]));
+        }
+        Ok(())
+    }
+
     pub fn convert_board_to_string(board: &Board) -> String {
         let mut fen: String = "".to_string();
         let mut empty_field_counter: i32 = 0;
@@ -257,11 +305,10 @@ impl Converter {
         }
 
         // get half moves information
-        // not implemented yet, hardcoded dummy value
-        fen += " 0";
+        fen += " ";
+        fen += board.halfmove_clock().to_string().as_str();
 
         // get number of move
-        // not implemented yet, hardcoded dummy value
         fen += " ";
         fen += board.move_counter().to_string().as_str();
 
@@ -1,65 +1,113 @@
-use std::future::{Future, IntoFuture};
 use log::{debug, info, trace};
 use crate::converter::converter::Converter;
 use crate::datamodel::board::Board;
 use crate::datamodel::options::Options;
 
 use tokio_util::sync::CancellationToken;
-use tokio::{runtime, select, task};
-use tokio::runtime::Runtime;
+use tokio::task;
 use tokio::task::JoinHandle;
 use crate::controller::Controller;
-use crate::datamodel::chess_move::ChessMove;
+use crate::datamodel::enums::color::Color;
+use crate::datamodel::search_limits::SearchLimits;
+use crate::evaluation::configured_evaluator::ConfiguredEvaluator;
+use crate::evaluation::Evaluator;
+use crate::move_provider::search_control::SearchControl;
 
 
-pub struct CancellationError;
-
 pub struct Organizer {
     options: Options,
     board: Board,
     board_string: String,
     move_strings: Vec<String>,
-    cancellation_token: Option<CancellationToken>
+    search_control: SearchControl,
 }
 
 impl Organizer {
-    pub fn new(options: Options, fen_board: String, moves: Vec<String>) -> Organizer {
+    pub fn new(options: Options, fen_board: String, moves: Vec<String>, search_limits: SearchLimits) -> Organizer {
         Organizer {
             options,
             board: Converter::convert_string_to_board(fen_board.clone()),
             board_string: fen_board,
             move_strings: moves,
-            cancellation_token: None,
+            search_control: SearchControl::new(search_limits, CancellationToken::new()),
         }
     }
 
     /// Calculate the next best move to be executed on the actual board.
-    /// Returns a future that contains the move as uci compatible move string once the calculation is done.
-    pub fn calculate_next_move_async(&mut self) -> JoinHandle<Result<String, CancellationError>> {
-
-        let cancellation_token = CancellationToken::new();
+    /// Returns a future that contains the move and the move to ponder on as uci compatible move
+    /// strings once the calculation is done.
+    /// The future of a ponder or infinite search is not done before the gui allows to send the move.
+    pub fn calculate_next_move_async(&mut self) -> JoinHandle<(String, Option<String>)> {
 
-        self.cancellation_token = Some(cancellation_token.clone());
+        let search_control = self.search_control.clone();
 
         let moves = self.move_strings.clone();
         let board = self.board.clone();
-        let options = self.options.clone();
+        let mut options = self.options.clone();
+        options.set_search_control(search_control.clone());
 
         info!("Spawning async thread");
         tokio::spawn( async move {
-            select! {
-                _ = cancellation_token.cancelled() => Err(CancellationError),
-                result = Self::calculate_next_move(moves, board, options) => {
-                    Ok(result)
-                }
-            }
+            // the search itself is blocking and polls the cancellation token of the search control
+            let result = task::spawn_blocking(move || Self::calculate_next_move(moves, board, options))
+                .await
+                .unwrap();
+
+            search_control.wait_until_released().await;
+            result
         })
     }
 
     /// Calculate the next best move to be executed on the actual board.
-    /// Returns the best move as uci compatible move string.
-    async fn calculate_next_move(move_strings: Vec<String>, mut board: Board, options: Options) -> String {
+    /// Returns the best move and the move to ponder on as uci compatible move strings.
+    fn calculate_next_move(move_strings: Vec<String>, board: Board, options: Options) -> (String, Option<String>) {
+        let board = Self::play_moves(&move_strings, board);
+        options.search_control().start(board.next_color());
+
+        debug!("giving position to controller");
+        let (best_move, ponder_move) = Controller::give_move(&board, &options).unwrap();
+
+        info!("best move is {0:?}, ponder move is {1:?}", best_move, ponder_move);
+        (
+            Converter::convert_move_to_string(&best_move),
+            ponder_move.map(|ponder_move| Converter::convert_move_to_string(&ponder_move)),
+        )
+    }
+
+    /// The opponent played the move the engine is pondering on.
+    pub fn ponderhit(&self) {
+        self.search_control.ponderhit();
+    }
+
+    pub fn stop_calculations(&self) {
+        self.search_control.stop();
+    }
 
+    /// Prints the evaluation of the actual board split into its terms.
+    pub fn print_evaluation(&self) {
+        let board = Self::play_moves(&self.move_strings, self.board.clone());
+        let mut evaluator = ConfiguredEvaluator::new();
+        evaluator.configure(&self.options);
+
+        match evaluator.trace(&board) {
+            Some(trace) => println!("{}", trace),
+            None => {
+                // the network has no terms, so only give its result
+                let rating = evaluator.evaluate(&board);
+                let rating = if board.next_color() == Color::WHITE { rating } else { -rating };
+                println!("The network evaluation has no terms");
+                println!("Total evaluation: {} cp from the view of white", rating);
+            }
+        }
+        info!("Printed evaluation of position {:?}", Converter::convert_board_to_string(&board));
+    }
+
+    /// Returns the board of the position command, with its moves played.
+    pub fn board_of_position(fen_board: &str, move_strings: &[String]) -> Board {
+        Self::play_moves(move_strings, Converter::convert_string_to_board(fen_board.to_string()))
+    }
+
+    fn play_moves(move_strings: &[String], mut board: Board) -> Board {
         move_strings.iter().for_each(|m| {
             trace!("playing move {}", m);
             trace!("move is {0:?}", Converter::convert_string_to_move(&m.clone()));
@@ -67,14 +115,6 @@ impl Organizer {
             trace!("played move {}", m);
         }
         );
-        debug!("giving position to controller");
-        let best_move: ChessMove = Controller::give_move(&board, &options).unwrap();
-
-        info!("best move is {0:?}", best_move);
-        Converter::convert_move_to_string(&best_move)
-    }
-
-    pub fn stop_calculations(&self) {
-        self.cancellation_token.as_ref().unwrap().cancel();
+        board
     }
-}
\ No newline at end of file
+}
@@ -5,9 +5,14 @@ use log::{info, warn};
 use log4rs::Handle;
 use crate::datamodel::enums::difficulty::Difficulty;
 use crate::datamodel::options::Options;
+use crate::datamodel::search_limits::SearchLimits;
 
 use strum::IntoEnumIterator;
 use crate::converter::organizer::Organizer;
+use crate::controller::Controller;
+use crate::move_provider::openings::{EXPLORER_DATABASES, MAX_EXPLORER_MIN_GAMES, MAX_EXPLORER_TOP_GAMES};
+use crate::move_provider::polyglot_book::MAX_BOOK_VARIETY;
+use crate::move_provider::response_cache::{MAX_CACHE_SIZE, MAX_CACHE_TTL};
 use crate::logging;
 use crate::logging::LoggingConfig;
 
@@ -15,10 +20,15 @@ pub struct RequestHandler;
 
 const ENGINE_NAME: &str = "Flengine";
 const ENGINE_AUTHOR: &str = "TeamFlyndre";
+const MAX_MULTI_PV: u32 = 10;
+const MAX_THREADS: u32 = 64;
 
 impl RequestHandler {
-    pub fn start_up(mut logging_config: LoggingConfig, logging_handle: Handle) -> () {
+    /// Answers the requests of the gui until it quits.
+    /// The given evaluation parameters file is the default of the EvalParams option, empty for none.
+    pub fn start_up(mut logging_config: LoggingConfig, logging_handle: Handle, eval_params: String) {
         let mut options = Options::from_default();
+        options.set_eval_params(eval_params);
 
         let mut position: String = String::new();
         let mut moves: Vec<String> = Vec::new();
@@ -59,6 +69,45 @@ impl RequestHandler {
                             + options.recursion_depth().to_string().as_str()
                             + " min 1 max 10";
                         println!("{}", recursive_depth_options);
+                        // pondering
+                        println!("option name Ponder type check default {}", options.ponder());
+                        // number of reported lines
+                        println!("option name MultiPV type spin default {} min 1 max {}", options.multi_pv(), MAX_MULTI_PV);
+                        // search threads
+                        println!("option name Threads type spin default {} min 1 max {}", options.threads(), MAX_THREADS);
+                        // selective search, can be switched off for debugging
+                        println!("option name NullMovePruning type check default {}", options.null_move_pruning());
+                        println!("option name LateMoveReductions type check default {}", options.late_move_reductions());
+                        println!("option name CheckExtensions type check default {}", options.check_extensions());
+                        // mate search
+                        println!("option name MateSearchChecksOnly type check default {}", options.mate_search_checks_only());
+                        // neural network evaluation
+                        println!("option name EvalFile type string default <empty>");
+                        // weights of the evaluation
+                        println!("option name EvalParams type string default {}",
+                            if options.eval_params().is_empty() { "<empty>" } else { options.eval_params() });
+                        // opening book
+                        println!("option name BookFile type string default <empty>");
+                        println!("option name BookVariety type spin default {} min 0 max {}", options.book_variety(), MAX_BOOK_VARIETY);
+                        // local endgame tablebases
+                        println!("option name SyzygyPath type string default <empty>");
+                        // order and conditions of the move providers
+                        println!("option name Pipeline type string default {}", options.pipeline());
+                        println!("option name PipelineFile type string default <empty>");
+                        // online opening explorer and tablebase
+                        println!("option name ExplorerUrl type string default {}", options.explorer_url());
+                        println!("option name ExplorerDatabase type combo default {} {}", options.explorer_database(),
+                            EXPLORER_DATABASES.iter().map(|database| format!("var {}", database)).collect::<Vec<String>>().join(" "));
+                        println!("option name ExplorerRatings type string default <empty>");
+                        println!("option name ExplorerSpeeds type string default <empty>");
+                        println!("option name ExplorerTopGames type spin default {} min 0 max {}", options.explorer_top_games(), MAX_EXPLORER_TOP_GAMES);
+                        println!("option name ExplorerPlayer type string default <empty>");
+                        println!("option name ExplorerMinGames type spin default {} min 0 max {}", options.explorer_min_games(), MAX_EXPLORER_MIN_GAMES);
+                        println!("option name TablebaseUrl type string default {}", options.tablebase_url());
+                        // cache of the answers of the web services
+                        println!("option name CacheFile type string default <empty>");
+                        println!("option name CacheTtl type spin default {} min 0 max {}", options.cache_ttl(), MAX_CACHE_TTL);
+                        println!("option name CacheSize type spin default {} min 0 max {}", options.cache_size(), MAX_CACHE_SIZE);
                         // uciok
                         println!("uciok");
                         info!("Finished initial communication with gui.");
@@ -88,6 +137,188 @@ impl RequestHandler {
                                         warn!("The value [{}] is not a valid number.", splitted_input[4]);
                                     }
                                 }
+                                "Ponder" => {
+                                    if let Ok(ponder) = splitted_input[4].parse::<bool>() {
+                                        info!("Changed option ponder to [{}]", ponder);
+                                        options.set_ponder(ponder);
+                                    } else {
+                                        warn!("The value [{}] is not a valid boolean.", splitted_input[4]);
+                                    }
+                                }
+                                "MultiPV" => {
+                                    if let Ok(multi_pv) = splitted_input[4].parse::<u32>() {
+                                        let multi_pv = multi_pv.clamp(1, MAX_MULTI_PV);
+                                        info!("Changed option multiPV to [{}]", multi_pv);
+                                        options.set_multi_pv(multi_pv);
+                                    } else {
+                                        warn!("The value [{}] is not a valid number.", splitted_input[4]);
+                                    }
+                                }
+                                "Threads" => {
+                                    if let Ok(threads) = splitted_input[4].parse::<u32>() {
+                                        let threads = threads.clamp(1, MAX_THREADS);
+                                        info!("Changed option threads to [{}]", threads);
+                                        options.set_threads(threads);
+                                    } else {
+                                        warn!("The value [{}] is not a valid number.", splitted_input[4]);
+                                    }
+                                }
+                                "NullMovePruning" => {
+                                    if let Ok(null_move_pruning) = splitted_input[4].parse::<bool>() {
+                                        info!("Changed option nullMovePruning to [{}]", null_move_pruning);
+                                        options.set_null_move_pruning(null_move_pruning);
+                                    } else {
+                                        warn!("The value [{}] is not a valid boolean.", splitted_input[4]);
+                                    }
+                                }
+                                "LateMoveReductions" => {
+                                    if let Ok(late_move_reductions) = splitted_input[4].parse::<bool>() {
+                                        info!("Changed option lateMoveReductions to [{}]", late_move_reductions);
+                                        options.set_late_move_reductions(late_move_reductions);
+                                    } else {
+                                        warn!("The value [{}] is not a valid boolean.", splitted_input[4]);
+                                    }
+                                }
+                                "CheckExtensions" => {
+                                    if let Ok(check_extensions) = splitted_input[4].parse::<bool>() {
+                                        info!("Changed option checkExtensions to [{}]", check_extensions);
+                                        options.set_check_extensions(check_extensions);
+                                    } else {
+                                        warn!("The value [{}] is not a valid boolean.", splitted_input[4]);
+                                    }
+                                }
+                                "MateSearchChecksOnly" => {
+                                    if let Ok(mate_search_checks_only) = splitted_input[4].parse::<bool>() {
+                                        info!("Changed option mateSearchChecksOnly to [{}]", mate_search_checks_only);
+                                        options.set_mate_search_checks_only(mate_search_checks_only);
+                                    } else {
+                                        warn!("The value [{}] is not a valid boolean.", splitted_input[4]);
+                                    }
+                                }
+                                "EvalFile" => {
+                                    // the path may contain spaces
+                                    let eval_file = splitted_input.iter().skip(4).cloned().collect::<Vec<&str>>().join(" ");
+                                    let eval_file = if eval_file == "<empty>" { String::new() } else { eval_file };
+                                    info!("Changed option evalFile to [{}]", eval_file);
+                                    options.set_eval_file(eval_file);
+                                }
+                                "EvalParams" => {
+                                    // the path may contain spaces
+                                    let eval_params = splitted_input.iter().skip(4).cloned().collect::<Vec<&str>>().join(" ");
+                                    let eval_params = if eval_params == "<empty>" { String::new() } else { eval_params };
+                                    info!("Changed option evalParams to [{}]", eval_params);
+                                    options.set_eval_params(eval_params);
+                                }
+                                "BookFile" => {
+                                    // the path may contain spaces
+                                    let book_file = splitted_input.iter().skip(4).cloned().collect::<Vec<&str>>().join(" ");
+                                    let book_file = if book_file == "<empty>" { String::new() } else { book_file };
+                                    info!("Changed option bookFile to [{}]", book_file);
+                                    options.set_book_file(book_file);
+                                }
+                                "BookVariety" => {
+                                    if let Ok(book_variety) = splitted_input[4].parse::<u32>() {
+                                        let book_variety = book_variety.min(MAX_BOOK_VARIETY);
+                                        info!("Changed option bookVariety to [{}]", book_variety);
+                                        options.set_book_variety(book_variety);
+                                    } else {
+                                        warn!("The value [{}] is not a valid book variety.", splitted_input[4]);
+                                    }
+                                }
+                                "SyzygyPath" => {
+                                    // the path may contain spaces
+                                    let syzygy_path = splitted_input.iter().skip(4).cloned().collect::<Vec<&str>>().join(" ");
+                                    let syzygy_path = if syzygy_path == "<empty>" { String::new() } else { syzygy_path };
+                                    info!("Changed option syzygyPath to [{}]", syzygy_path);
+                                    options.set_syzygy_path(syzygy_path);
+                                }
+                                "Pipeline" => {
+                                    let pipeline = splitted_input.iter().skip(4).cloned().collect::<Vec<&str>>().join(" ");
+                                    let pipeline = if pipeline == "<empty>" { String::new() } else { pipeline };
+                                    info!("Changed option pipeline to [{}]", pipeline);
+                                    options.set_pipeline(pipeline);
+                                }
+                                "PipelineFile" => {
+                                    // the path may contain spaces
+                                    let pipeline_file = splitted_input.iter().skip(4).cloned().collect::<Vec<&str>>().join(" ");
+                                    let pipeline_file = if pipeline_file == "<empty>" { String::new() } else { pipeline_file };
+                                    info!("Changed option pipelineFile to [{}]", pipeline_file);
+                                    options.set_pipeline_file(pipeline_file);
+                                }
+                                "ExplorerUrl" => {
+                                    info!("Changed option explorerUrl to [{}]", splitted_input[4]);
+                                    options.set_explorer_url(splitted_input[4].to_string());
+                                }
+                                "ExplorerDatabase" => {
+                                    if EXPLORER_DATABASES.contains(&splitted_input[4]) {
+                                        info!("Changed option explorerDatabase to [{}]", splitted_input[4]);
+                                        options.set_explorer_database(splitted_input[4].to_string());
+                                    } else {
+                                        warn!("The value [{}] is not a valid explorer database.", splitted_input[4]);
+                                    }
+                                }
+                                "ExplorerRatings" => {
+                                    let explorer_ratings = if splitted_input[4] == "<empty>" { String::new() } else { splitted_input[4].to_string() };
+                                    info!("Changed option explorerRatings to [{}]", explorer_ratings);
+                                    options.set_explorer_ratings(explorer_ratings);
+                                }
+                                "ExplorerSpeeds" => {
+                                    let explorer_speeds = if splitted_input[4] == "<empty>" { String::new() } else { splitted_input[4].to_string() };
+                                    info!("Changed option explorerSpeeds to [{}]", explorer_speeds);
+                                    options.set_explorer_speeds(explorer_speeds);
+                                }
+                                "ExplorerTopGames" => {
+                                    if let Ok(explorer_top_games) = splitted_input[4].parse::<u32>() {
+                                        let explorer_top_games = explorer_top_games.min(MAX_EXPLORER_TOP_GAMES);
+                                        info!("Changed option explorerTopGames to [{}]", explorer_top_games);
+                                        options.set_explorer_top_games(explorer_top_games);
+                                    } else {
+                                        warn!("The value [{}] is not a valid number of top games.", splitted_input[4]);
+                                    }
+                                }
+                                "ExplorerPlayer" => {
+                                    let explorer_player = if splitted_input[4] == "<empty>" { String::new() } else { splitted_input[4].to_string() };
+                                    info!("Changed option explorerPlayer to [{}]", explorer_player);
+                                    options.set_explorer_player(explorer_player);
+                                }
+                                "ExplorerMinGames" => {
+                                    if let Ok(explorer_min_games) = splitted_input[4].parse::<u32>() {
+                                        let explorer_min_games = explorer_min_games.min(MAX_EXPLORER_MIN_GAMES);
+                                        info!("Changed option explorerMinGames to [{}]", explorer_min_games);
+                                        options.set_explorer_min_games(explorer_min_games);
+                                    } else {
+                                        warn!("The value [{}] is not a valid number of games.", splitted_input[4]);
+                                    }
+                                }
+                                "TablebaseUrl" => {
+                                    info!("Changed option tablebaseUrl to [{}]", splitted_input[4]);
+                                    options.set_tablebase_url(splitted_input[4].to_string());
+                                }
+                                "CacheFile" => {
+                                    // the path may contain spaces
+                                    let cache_file = splitted_input.iter().skip(4).cloned().collect::<Vec<&str>>().join(" ");
+                                    let cache_file = if cache_file == "<empty>" { String::new() } else { cache_file };
+                                    info!("Changed option cacheFile to [{}]", cache_file);
+                                    options.set_cache_file(cache_file);
+                                }
+                                "CacheTtl" => {
+                                    if let Ok(cache_ttl) = splitted_input[4].parse::<u32>() {
+                                        let cache_ttl = cache_ttl.min(MAX_CACHE_TTL);
+                                        info!("Changed option cacheTtl to [{}]", cache_ttl);
+                                        options.set_cache_ttl(cache_ttl);
+                                    } else {
+                                        warn!("The value [{}] is not a valid number of hours.", splitted_input[4]);
+                                    }
+                                }
+                                "CacheSize" => {
+                                    if let Ok(cache_size) = splitted_input[4].parse::<u32>() {
+                                        let cache_size = cache_size.min(MAX_CACHE_SIZE);
+                                        info!("Changed option cacheSize to [{}]", cache_size);
+                                        options.set_cache_size(cache_size);
+                                    } else {
+                                        warn!("The value [{}] is not a valid cache size.", splitted_input[4]);
+                                    }
+                                }
                                 _ => {
                                     warn!("The value [{}] is not a supported option", splitted_input[2]);
                                 }
@@ -99,14 +330,16 @@ impl RequestHandler {
                         println!("readyok");
                     }
                     "ucinewgame" => {
-                        // ignore
                         info!("Recognized ucinewgame.");
+                        Controller::new_game();
                     }
                     "position" => {
                         // get position
                         info!("Recognized position command.");
                         if splitted_input.len() > 1 {
                             position = splitted_input[1].to_string();
+                            // the moves of the previous position do not belong to the new one
+                            moves.clear();
 
                             match position.as_str() {
                                 "fen" => {
@@ -138,29 +371,72 @@ impl RequestHandler {
                                 }
                                 _ => { panic!("The value [{}] is not a valid position.", position); }
                             };
+                            Controller::position_changed(&Organizer::board_of_position(&position, &moves));
                             
                             // computing is started with the go command
                         }
                     }
                     "go" => {
                         info!("Recognized go command. Starting calculation...");
+                        // read the search limits
+                        // go [ponder] [wtime <x>] [btime <x>] [winc <x>] [binc <x>] [movestogo <x>] [depth <x>] [mate <x>] [movetime <x>] [infinite]
+                        let mut search_limits = SearchLimits::new();
+                        let mut parameters = splitted_input.iter().skip(1);
+                        while let Some(parameter) = parameters.next() {
+                            match *parameter {
+                                "ponder" => search_limits.set_ponder(true),
+                                "infinite" => search_limits.set_infinite(true),
+                                "wtime" => search_limits.set_white_time(parameters.next().and_then(|v| v.parse().ok())),
+                                "btime" => search_limits.set_black_time(parameters.next().and_then(|v| v.parse().ok())),
+                                "winc" => search_limits.set_white_increment(parameters.next().and_then(|v| v.parse().ok())),
+                                "binc" => search_limits.set_black_increment(parameters.next().and_then(|v| v.parse().ok())),
+                                "movestogo" => search_limits.set_moves_to_go(parameters.next().and_then(|v| v.parse().ok())),
+                                "movetime" => search_limits.set_move_time(parameters.next().and_then(|v| v.parse().ok())),
+                                "depth" => search_limits.set_depth(parameters.next().and_then(|v| v.parse().ok())),
+                                "mate" => search_limits.set_mate(parameters.next().and_then(|v| v.parse().ok())),
+                                _ => {
+                                    warn!("The value [{}] is not a supported go parameter", parameter);
+                                }
+                            }
+                        }
+                        info!("Search limits are [{:?}]", search_limits);
+
                         organizer = Some(Organizer::new(
                             options.clone(),
                             position.clone(),
                             moves.clone(),
+                            search_limits,
                         ));
                         let future_move = organizer.as_mut().unwrap().calculate_next_move_async();
-                        let future_move = future_move.then(|mut s| async move {
-                            let result = s.unwrap();
-                            info!("Calculation finished. Best move: {}", result.as_ref().ok().unwrap());
-                            println!("bestmove {}", result.as_ref().ok().unwrap());
+                        let future_move = future_move.then(|result| async move {
+                            let (best_move, ponder_move) = result.unwrap();
+                            info!("Calculation finished. Best move: {}, ponder move: {:?}", best_move, ponder_move);
+                            match ponder_move {
+                                Some(ponder_move) => println!("bestmove {} ponder {}", best_move, ponder_move),
+                                None => println!("bestmove {}", best_move),
+                            }
                         });
                         tokio::spawn(future_move);
                     }
+                    "eval" => {
+                        // non standard command, prints the terms of the evaluation of the current position
+                        info!("Recognized eval command.");
+                        let position = if position.is_empty() { "startpos".to_string() } else { position.clone() };
+                        Organizer::new(options.clone(), position, moves.clone(), SearchLimits::new()).print_evaluation();
+                    }
+                    "ponderhit" => {
+                        // the opponent played the expected move, the ponder search continues as normal search
+                        info!("Recognized ponderhit command.");
+                        if let Some(organizer) = organizer.as_ref() {
+                            organizer.ponderhit();
+                        }
+                    }
                     "stop" => {
                         // indicate gui asked to send the move
-                        // not used yet
                         info!("Recognized stop command.");
+                        if let Some(organizer) = organizer.as_ref() {
+                            organizer.stop_calculations();
+                        }
                     }
                     "debug" => {
                         info!("Recognized debug command.");
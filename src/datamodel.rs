@@ -4,3 +4,6 @@ pub mod board;
 pub mod field;
 pub mod chess_move;
 pub mod options;
+pub mod search_limits;
+pub mod zobrist;
+pub mod polyglot_zobrist;
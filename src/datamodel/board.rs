@@ -8,6 +8,14 @@ use crate::datamodel::enums::file::File;
 use crate::datamodel::piece::Piece;
 use super::field::Field;
 
+/// A field whose piece was changed by a move.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FieldChange {
+    pub field: Field,
+    pub removed: Option<Piece>,
+    pub added: Option<Piece>,
+}
+
 #[derive(Clone)]
 pub struct Board {
     /// Represents the en passant field.
@@ -24,6 +32,9 @@ pub struct Board {
     /// Number of the next move to be done on the board. This is important for fen string support.
     move_counter: u16,
 
+    /// Number of plies since the last capture or pawn move, for the 50-move rule
+    halfmove_clock: u16,
+
     /// Indicates whether white has short castling rights
     white_can_castle_short: bool,
     /// Indicates whether white has long castling rights
@@ -42,6 +53,7 @@ impl Board {
             pieces: [const {[const { None }; 8]}; 8],
             next_color: Color::WHITE,
             move_counter: 1,
+            halfmove_clock: 0,
             white_can_castle_short: true,
             white_can_castle_long: true,
             black_can_castle_short: true,
@@ -59,6 +71,8 @@ impl Board {
 
     pub fn play_move(&mut self, chess_move: &ChessMove) -> () {
         let piece_from = self.get_piece(chess_move.from_field()).unwrap_or_else(|| panic!("Cannot play move. There is no piece on field {:?}", chess_move.from_field()));
+        // captures and pawn moves reset the 50-move rule, en passant is a pawn move as well
+        let is_zeroing = piece_from.piece_type() == PieceType::PAWN || self.get_piece(chess_move.to_field()).is_some();
 
         // check whether move affects future castling rights and set flags accordingly
         if piece_from.piece_type() == PieceType::KING {
@@ -163,12 +177,44 @@ impl Board {
             self.en_passant_field = None;
         }
 
+        // the move number grows after the move of black
+        if self.next_color == Color::BLACK {
+            self.move_counter += 1;
+        }
+        self.halfmove_clock = if is_zeroing { 0 } else { self.halfmove_clock + 1 };
+
         self.next_color = match self.next_color {
             Color::BLACK => Color::WHITE,
             Color::WHITE => Color::BLACK,
         };
+    }
 
-        self.move_counter += 1;
+    /// Plays the move and returns the fields it changed, including captured pawns en passant
+    /// and rooks moved by castling.
+    pub fn play_move_with_changes(&mut self, chess_move: &ChessMove) -> Vec<FieldChange> {
+        let pieces_before = self.pieces;
+        self.play_move(chess_move);
+
+        let mut changes = Vec::new();
+        for (rank, (before, after)) in pieces_before.iter().zip(self.pieces.iter()).enumerate() {
+            for (file, (removed, added)) in before.iter().zip(after.iter()).enumerate() {
+                if removed != added {
+                    changes.push(FieldChange {
+                        field: Field::new(File::from_index(file), Rank::from_index(rank)),
+                        removed: *removed,
+                        added: *added,
+                    });
+                }
+            }
+        }
+        changes
+    }
+
+    /// Passes the turn to the opponent without moving a piece.
+    /// This is not a legal move, it is only used by the search to detect threats.
+    pub fn play_null_move(&mut self) {
+        self.en_passant_field = None;
+        self.next_color = !self.next_color;
     }
 
     pub fn piece_count(&self) -> u16 {
@@ -199,6 +245,10 @@ impl Board {
         self.move_counter
     }
 
+    pub fn halfmove_clock(&self) -> u16 {
+        self.halfmove_clock
+    }
+
     pub fn next_color(&self) -> Color {
         self.next_color
     }
@@ -219,6 +269,10 @@ impl Board {
         self.move_counter = move_counter;
     }
 
+    pub fn set_halfmove_clock(&mut self, halfmove_clock: u16) {
+        self.halfmove_clock = halfmove_clock;
+    }
+
     pub fn set_white_can_castle_short(&mut self, white_can_castle_short: bool) {
         self.white_can_castle_short = white_can_castle_short;
     }
@@ -1,7 +1,7 @@
 use crate::datamodel::enums::piece_type::PieceType;
 use crate::datamodel::field::Field;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct ChessMove {
     from_field: Field,
     to_field: Field,
@@ -11,14 +11,14 @@ pub enum PieceType {
 }
 
 impl PieceType {
-    pub fn value(&self) -> u8 {
+    pub fn to_index(self) -> usize {
         match self {
-            PieceType::PAWN => 1,
-            PieceType::ROOK => 5,
-            PieceType::BISHOP => 3,
+            PieceType::PAWN => 0,
+            PieceType::ROOK => 1,
+            PieceType::BISHOP => 2,
             PieceType::KNIGHT => 3,
-            PieceType::QUEEN => 9,
-            PieceType::KING => 100,
+            PieceType::QUEEN => 4,
+            PieceType::KING => 5,
         }
     }
 }
\ No newline at end of file
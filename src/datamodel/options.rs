@@ -1,9 +1,65 @@
 use crate::datamodel::enums::difficulty::Difficulty;
+use crate::controller::pipeline::DEFAULT_PIPELINE;
+use crate::move_provider::endgame::DEFAULT_TABLEBASE_URL;
+use crate::move_provider::openings::{DEFAULT_EXPLORER_DATABASE, DEFAULT_EXPLORER_URL};
+use crate::move_provider::search_control::SearchControl;
 
 #[derive(Clone)]
 pub struct Options {
     difficulty: Difficulty,
     recursion_depth: u32,
+    /// Indicates whether the gui allows the engine to ponder
+    ponder: bool,
+    /// Number of best lines reported to the gui
+    multi_pv: u32,
+    /// Number of threads searching the position
+    threads: u32,
+    /// Enables null move pruning in the search
+    null_move_pruning: bool,
+    /// Enables reduced search depth for late quiet moves
+    late_move_reductions: bool,
+    /// Enables deeper search of positions in check
+    check_extensions: bool,
+    /// Restricts the moves of the mating player to checks in the mate search
+    mate_search_checks_only: bool,
+    /// Path of the network file for the neural network evaluation, empty for the classical evaluation
+    eval_file: String,
+    /// Path of the toml or json file with the weights of the evaluation, empty for the built in weights
+    eval_params: String,
+    /// Path of the Polyglot opening book, empty for no book
+    book_file: String,
+    /// Randomness of the choice of the book moves from 0 for the best move to 100 for moves in proportion to their weights
+    book_variety: u32,
+    /// Directories of the local Syzygy tablebases separated like the PATH variable, empty for none
+    syzygy_path: String,
+    /// The move providers that are asked for a move, in the syntax of PipelineConfig::parse
+    pipeline: String,
+    /// Path of a toml or json file with the pipeline, which takes precedence over the pipeline option
+    pipeline_file: String,
+    /// Base url of the Lichess opening explorer or a self hosted instance
+    explorer_url: String,
+    /// Database of the opening explorer: masters, lichess or player
+    explorer_database: String,
+    /// Rating bands of the lichess database separated by commas, empty for all
+    explorer_ratings: String,
+    /// Speeds of the lichess and player databases separated by commas, empty for all
+    explorer_speeds: String,
+    /// Number of top games the opening explorer returns with the moves
+    explorer_top_games: u32,
+    /// Name of the player whose games the player database contains
+    explorer_player: String,
+    /// Minimum number of games of an explorer move, rarer moves are not played
+    explorer_min_games: u32,
+    /// Base url of the Lichess tablebase or a self hosted instance
+    tablebase_url: String,
+    /// Path of the file the answers of the web services are cached in, empty to cache in memory only
+    cache_file: String,
+    /// Hours after which a cached answer is requested again
+    cache_ttl: u32,
+    /// Maximum number of cached answers
+    cache_size: u32,
+    /// Controls the currently running search
+    search_control: SearchControl,
 }
 
 impl Options {
@@ -15,6 +71,110 @@ impl Options {
         self.recursion_depth
     }
 
+    pub fn ponder(&self) -> bool {
+        self.ponder
+    }
+
+    pub fn multi_pv(&self) -> u32 {
+        self.multi_pv
+    }
+
+    pub fn threads(&self) -> u32 {
+        self.threads
+    }
+
+    pub fn null_move_pruning(&self) -> bool {
+        self.null_move_pruning
+    }
+
+    pub fn late_move_reductions(&self) -> bool {
+        self.late_move_reductions
+    }
+
+    pub fn check_extensions(&self) -> bool {
+        self.check_extensions
+    }
+
+    pub fn mate_search_checks_only(&self) -> bool {
+        self.mate_search_checks_only
+    }
+
+    pub fn eval_file(&self) -> &str {
+        &self.eval_file
+    }
+
+    pub fn eval_params(&self) -> &str {
+        &self.eval_params
+    }
+
+    pub fn book_file(&self) -> &str {
+        &self.book_file
+    }
+
+    pub fn book_variety(&self) -> u32 {
+        self.book_variety
+    }
+
+    pub fn syzygy_path(&self) -> &str {
+        &self.syzygy_path
+    }
+
+    pub fn pipeline(&self) -> &str {
+        &self.pipeline
+    }
+
+    pub fn pipeline_file(&self) -> &str {
+        &self.pipeline_file
+    }
+
+    pub fn explorer_url(&self) -> &str {
+        &self.explorer_url
+    }
+
+    pub fn explorer_database(&self) -> &str {
+        &self.explorer_database
+    }
+
+    pub fn explorer_ratings(&self) -> &str {
+        &self.explorer_ratings
+    }
+
+    pub fn explorer_speeds(&self) -> &str {
+        &self.explorer_speeds
+    }
+
+    pub fn explorer_top_games(&self) -> u32 {
+        self.explorer_top_games
+    }
+
+    pub fn explorer_player(&self) -> &str {
+        &self.explorer_player
+    }
+
+    pub fn explorer_min_games(&self) -> u32 {
+        self.explorer_min_games
+    }
+
+    pub fn tablebase_url(&self) -> &str {
+        &self.tablebase_url
+    }
+
+    pub fn cache_file(&self) -> &str {
+        &self.cache_file
+    }
+
+    pub fn cache_ttl(&self) -> u32 {
+        self.cache_ttl
+    }
+
+    pub fn cache_size(&self) -> u32 {
+        self.cache_size
+    }
+
+    pub fn search_control(&self) -> &SearchControl {
+        &self.search_control
+    }
+
     pub fn set_difficulty(&mut self, difficulty: Difficulty) {
         self.difficulty = difficulty;
     }
@@ -23,14 +183,173 @@ impl Options {
         self.recursion_depth = recursion_depth;
     }
 
+    pub fn set_ponder(&mut self, ponder: bool) {
+        self.ponder = ponder;
+    }
+
+    pub fn set_multi_pv(&mut self, multi_pv: u32) {
+        self.multi_pv = multi_pv;
+    }
+
+    pub fn set_threads(&mut self, threads: u32) {
+        self.threads = threads;
+    }
+
+    pub fn set_null_move_pruning(&mut self, null_move_pruning: bool) {
+        self.null_move_pruning = null_move_pruning;
+    }
+
+    pub fn set_late_move_reductions(&mut self, late_move_reductions: bool) {
+        self.late_move_reductions = late_move_reductions;
+    }
+
+    pub fn set_check_extensions(&mut self, check_extensions: bool) {
+        self.check_extensions = check_extensions;
+    }
+
+    pub fn set_mate_search_checks_only(&mut self, mate_search_checks_only: bool) {
+        self.mate_search_checks_only = mate_search_checks_only;
+    }
+
+    pub fn set_eval_file(&mut self, eval_file: String) {
+        self.eval_file = eval_file;
+    }
+
+    pub fn set_eval_params(&mut self, eval_params: String) {
+        self.eval_params = eval_params;
+    }
+
+    pub fn set_book_file(&mut self, book_file: String) {
+        self.book_file = book_file;
+    }
+
+    pub fn set_book_variety(&mut self, book_variety: u32) {
+        self.book_variety = book_variety;
+    }
+
+    pub fn set_syzygy_path(&mut self, syzygy_path: String) {
+        self.syzygy_path = syzygy_path;
+    }
+
+    pub fn set_pipeline(&mut self, pipeline: String) {
+        self.pipeline = pipeline;
+    }
+
+    pub fn set_pipeline_file(&mut self, pipeline_file: String) {
+        self.pipeline_file = pipeline_file;
+    }
+
+    pub fn set_explorer_url(&mut self, explorer_url: String) {
+        self.explorer_url = explorer_url;
+    }
+
+    pub fn set_explorer_database(&mut self, explorer_database: String) {
+        self.explorer_database = explorer_database;
+    }
+
+    pub fn set_explorer_ratings(&mut self, explorer_ratings: String) {
+        self.explorer_ratings = explorer_ratings;
+    }
+
+    pub fn set_explorer_speeds(&mut self, explorer_speeds: String) {
+        self.explorer_speeds = explorer_speeds;
+    }
+
+    pub fn set_explorer_top_games(&mut self, explorer_top_games: u32) {
+        self.explorer_top_games = explorer_top_games;
+    }
+
+    pub fn set_explorer_player(&mut self, explorer_player: String) {
+        self.explorer_player = explorer_player;
+    }
+
+    pub fn set_explorer_min_games(&mut self, explorer_min_games: u32) {
+        self.explorer_min_games = explorer_min_games;
+    }
+
+    pub fn set_tablebase_url(&mut self, tablebase_url: String) {
+        self.tablebase_url = tablebase_url;
+    }
+
+    pub fn set_cache_file(&mut self, cache_file: String) {
+        self.cache_file = cache_file;
+    }
+
+    pub fn set_cache_ttl(&mut self, cache_ttl: u32) {
+        self.cache_ttl = cache_ttl;
+    }
+
+    pub fn set_cache_size(&mut self, cache_size: u32) {
+        self.cache_size = cache_size;
+    }
+
+    pub fn set_search_control(&mut self, search_control: SearchControl) {
+        self.search_control = search_control;
+    }
+
     pub fn new(difficulty: Difficulty, recursion_depth: u32) -> Self {
-        Self { difficulty, recursion_depth }
+        Self {
+            difficulty,
+            recursion_depth,
+            ponder: false,
+            multi_pv: 1,
+            threads: 1,
+            null_move_pruning: true,
+            late_move_reductions: true,
+            check_extensions: true,
+            mate_search_checks_only: false,
+            eval_file: String::new(),
+            eval_params: String::new(),
+            book_file: String::new(),
+            book_variety: 100,
+            syzygy_path: String::new(),
+            pipeline: DEFAULT_PIPELINE.to_string(),
+            pipeline_file: String::new(),
+            explorer_url: DEFAULT_EXPLORER_URL.to_string(),
+            explorer_database: DEFAULT_EXPLORER_DATABASE.to_string(),
+            explorer_ratings: String::new(),
+            explorer_speeds: String::new(),
+            explorer_top_games: 0,
+            explorer_player: String::new(),
+            explorer_min_games: 10,
+            tablebase_url: DEFAULT_TABLEBASE_URL.to_string(),
+            cache_file: String::new(),
+            cache_ttl: 168,
+            cache_size: 100_000,
+            search_control: SearchControl::default(),
+        }
     }
 
     pub fn from_default() -> Self {
         Self {
             difficulty: Difficulty::NORMAL,
             recursion_depth: 4,
+            ponder: false,
+            multi_pv: 1,
+            threads: 1,
+            null_move_pruning: true,
+            late_move_reductions: true,
+            check_extensions: true,
+            mate_search_checks_only: false,
+            eval_file: String::new(),
+            eval_params: String::new(),
+            book_file: String::new(),
+            book_variety: 100,
+            syzygy_path: String::new(),
+            pipeline: DEFAULT_PIPELINE.to_string(),
+            pipeline_file: String::new(),
+            explorer_url: DEFAULT_EXPLORER_URL.to_string(),
+            explorer_database: DEFAULT_EXPLORER_DATABASE.to_string(),
+            explorer_ratings: String::new(),
+            explorer_speeds: String::new(),
+            explorer_top_games: 0,
+            explorer_player: String::new(),
+            explorer_min_games: 10,
+            tablebase_url: DEFAULT_TABLEBASE_URL.to_string(),
+            cache_file: String::new(),
+            cache_ttl: 168,
+            cache_size: 100_000,
+            search_control: SearchControl::default(),
         }
     }
-}
\ No newline at end of file
+}
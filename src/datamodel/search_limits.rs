@@ -0,0 +1,104 @@
+/// Holds the limits of a single search as given by the parameters of the uci go command.
+/// Times are given in milliseconds.
+#[derive(Clone, Debug, Default)]
+pub struct SearchLimits {
+    white_time: Option<u64>,
+    black_time: Option<u64>,
+    white_increment: Option<u64>,
+    black_increment: Option<u64>,
+    moves_to_go: Option<u32>,
+    move_time: Option<u64>,
+    depth: Option<u32>,
+    /// Number of moves in which a forced mate is searched
+    mate: Option<u32>,
+    /// Indicates that the search must not stop until the gui sends stop
+    infinite: bool,
+    /// Indicates that the search runs on the opponents time on the predicted position
+    ponder: bool,
+}
+
+impl SearchLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn white_time(&self) -> Option<u64> {
+        self.white_time
+    }
+
+    pub fn black_time(&self) -> Option<u64> {
+        self.black_time
+    }
+
+    pub fn white_increment(&self) -> Option<u64> {
+        self.white_increment
+    }
+
+    pub fn black_increment(&self) -> Option<u64> {
+        self.black_increment
+    }
+
+    pub fn moves_to_go(&self) -> Option<u32> {
+        self.moves_to_go
+    }
+
+    pub fn move_time(&self) -> Option<u64> {
+        self.move_time
+    }
+
+    pub fn depth(&self) -> Option<u32> {
+        self.depth
+    }
+
+    pub fn mate(&self) -> Option<u32> {
+        self.mate
+    }
+
+    pub fn infinite(&self) -> bool {
+        self.infinite
+    }
+
+    pub fn ponder(&self) -> bool {
+        self.ponder
+    }
+
+    pub fn set_white_time(&mut self, white_time: Option<u64>) {
+        self.white_time = white_time;
+    }
+
+    pub fn set_black_time(&mut self, black_time: Option<u64>) {
+        self.black_time = black_time;
+    }
+
+    pub fn set_white_increment(&mut self, white_increment: Option<u64>) {
+        self.white_increment = white_increment;
+    }
+
+    pub fn set_black_increment(&mut self, black_increment: Option<u64>) {
+        self.black_increment = black_increment;
+    }
+
+    pub fn set_moves_to_go(&mut self, moves_to_go: Option<u32>) {
+        self.moves_to_go = moves_to_go;
+    }
+
+    pub fn set_move_time(&mut self, move_time: Option<u64>) {
+        self.move_time = move_time;
+    }
+
+    pub fn set_depth(&mut self, depth: Option<u32>) {
+        self.depth = depth;
+    }
+
+    pub fn set_mate(&mut self, mate: Option<u32>) {
+        self.mate = mate;
+    }
+
+    pub fn set_infinite(&mut self, infinite: bool) {
+        self.infinite = infinite;
+    }
+
+    pub fn set_ponder(&mut self, ponder: bool) {
+        self.ponder = ponder;
+    }
+}
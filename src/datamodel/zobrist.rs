@@ -0,0 +1,98 @@
+use crate::datamodel::board::Board;
+use crate::datamodel::enums::color::Color;
+use crate::datamodel::enums::file::File;
+use crate::datamodel::enums::piece_type::PieceType;
+use crate::datamodel::enums::rank::Rank;
+use crate::datamodel::field::Field;
+
+/// Offset of the key for black to move
+const SIDE_TO_MOVE_KEY: usize = 768;
+/// Offset of the keys for the castling rights (white short, white long, black short, black long)
+const CASTLING_KEYS: usize = 769;
+/// Offset of the keys for the file of the en passant field
+const EN_PASSANT_KEYS: usize = 773;
+
+/// Random keys for every piece on every field, the side to move, the castling rights
+/// and the en passant file.
+const KEYS: [u64; 781] = generate_keys(0x466C656E67696E65);
+
+/// Generates pseudo random keys at compile time using the splitmix64 generator.
+const fn generate_keys<const N: usize>(seed: u64) -> [u64; N] {
+    let mut keys = [0u64; N];
+    let mut state = seed;
+    let mut i = 0;
+    while i < N {
+        state = state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        keys[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    keys
+}
+
+/// Computes zobrist hashes of positions.
+/// Equal positions have equal hashes, different positions have different hashes with high probability.
+pub struct Zobrist;
+
+impl Zobrist {
+    /// Returns the hash of the given position.
+    pub fn hash(board: &Board) -> u64 {
+        let mut hash: u64 = 0;
+
+        for rank in 0..8 {
+            for file in 0..8 {
+                let field = Field::new(File::from_index(file), Rank::from_index(rank));
+                if let Some(piece) = board.get_piece(&field) {
+                    hash ^= KEYS[piece_key_index(piece.color(), piece.piece_type().to_index(), rank * 8 + file)];
+                }
+            }
+        }
+
+        if board.next_color() == Color::BLACK {
+            hash ^= KEYS[SIDE_TO_MOVE_KEY];
+        }
+
+        let castling_rights = [
+            board.white_can_castle_short(),
+            board.white_can_castle_long(),
+            board.black_can_castle_short(),
+            board.black_can_castle_long(),
+        ];
+        for (index, _) in castling_rights.iter().enumerate().filter(|(_, right)| **right) {
+            hash ^= KEYS[CASTLING_KEYS + index];
+        }
+
+        if let Some(en_passant_field) = board.en_passant_field() {
+            hash ^= KEYS[EN_PASSANT_KEYS + en_passant_field.file().to_index()];
+        }
+
+        hash
+    }
+
+    /// Returns the hash of the pawns of the given position only.
+    pub fn pawn_hash(board: &Board) -> u64 {
+        let mut hash: u64 = 0;
+
+        for rank in 0..8 {
+            for file in 0..8 {
+                let field = Field::new(File::from_index(file), Rank::from_index(rank));
+                if let Some(piece) = board.get_piece(&field).filter(|piece| piece.piece_type() == PieceType::PAWN) {
+                    hash ^= KEYS[piece_key_index(piece.color(), PieceType::PAWN.to_index(), rank * 8 + file)];
+                }
+            }
+        }
+
+        hash
+    }
+}
+
+/// Returns the index of the key of a piece with the given color and type index on the given square.
+fn piece_key_index(color: Color, piece_type_index: usize, square: usize) -> usize {
+    let color_index = match color {
+        Color::WHITE => 0,
+        Color::BLACK => 1,
+    };
+    (color_index * 6 + piece_type_index) * 64 + square
+}
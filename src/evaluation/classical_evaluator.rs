@@ -0,0 +1,119 @@
+use crate::datamodel::board::Board;
+use crate::datamodel::enums::color::Color;
+use crate::datamodel::enums::piece_type::PieceType;
+use crate::evaluation::Evaluator;
+use crate::evaluation::evaluation_trace::EvaluationTrace;
+use crate::evaluation::evaluation_weights::EvaluationWeights;
+use crate::evaluation::king_safety::king_safety_score;
+use crate::evaluation::pawn_structure::{pawn_structure_score, PawnHashTable, Pawns};
+use crate::evaluation::piece_activity::{piece_activity_score, piece_activity_terms};
+use crate::evaluation::piece_square_evaluator::PieceSquareEvaluator;
+
+/// An implementation of Evaluator that adds positional terms to the piece square tables.
+///
+/// Every term is rated for the midgame and the endgame, the sum is blended by the game phase.
+#[derive(Debug)]
+pub struct ClassicalEvaluator {
+    piece_square_evaluator: PieceSquareEvaluator,
+    weights: EvaluationWeights,
+    /// None if the evaluator is only used once per position, then the table would not pay off
+    pawn_hash_table: Option<PawnHashTable>,
+}
+
+impl ClassicalEvaluator {
+    pub fn new() -> ClassicalEvaluator {
+        ClassicalEvaluator::with_weights(EvaluationWeights::default())
+    }
+
+    pub fn with_weights(weights: EvaluationWeights) -> ClassicalEvaluator {
+        ClassicalEvaluator {
+            piece_square_evaluator: PieceSquareEvaluator::with_weights(weights.piece_square.clone()),
+            weights,
+            pawn_hash_table: Some(PawnHashTable::new()),
+        }
+    }
+
+    /// Creates an evaluator without pawn hash table, which is cheap to build for every set of weights
+    /// while tuning. Each position is evaluated only once per set of weights, so a cache would never hit.
+    pub fn without_pawn_hash_table(weights: EvaluationWeights) -> ClassicalEvaluator {
+        ClassicalEvaluator {
+            piece_square_evaluator: PieceSquareEvaluator::with_weights(weights.piece_square.clone()),
+            weights,
+            pawn_hash_table: None,
+        }
+    }
+}
+
+impl Evaluator for ClassicalEvaluator {
+    fn evaluate(&self, board: &Board) -> i32 {
+        let weights = &self.weights;
+        let (mut score, phase) = self.piece_square_evaluator.score(board);
+        let pawns = Pawns::from_board(board);
+
+        score += match &self.pawn_hash_table {
+            Some(pawn_hash_table) => pawn_hash_table.score(board, &pawns, &weights.pawn_structure),
+            None => pawn_structure_score(&pawns, Color::WHITE, &weights.pawn_structure)
+                - pawn_structure_score(&pawns, Color::BLACK, &weights.pawn_structure),
+        };
+        score += king_safety_score(board, &pawns, Color::WHITE, &weights.king_safety)
+            - king_safety_score(board, &pawns, Color::BLACK, &weights.king_safety);
+        score += piece_activity_score(board, &pawns, Color::WHITE, &weights.piece_activity)
+            - piece_activity_score(board, &pawns, Color::BLACK, &weights.piece_activity);
+
+        let rating = score.taper(phase);
+        if board.next_color() == Color::WHITE { rating } else { -rating }
+    }
+
+    fn piece_value(&self, piece_type: PieceType) -> i32 {
+        self.weights.piece_square.piece_value(piece_type)
+    }
+
+    fn trace(&self, board: &Board) -> Option<EvaluationTrace> {
+        let weights = &self.weights;
+        let (_, phase) = self.piece_square_evaluator.score(board);
+        let pawns = Pawns::from_board(board);
+        let mut trace = EvaluationTrace::new(phase);
+
+        let [white_pieces, black_pieces] = [Color::WHITE, Color::BLACK]
+            .map(|color| self.piece_square_evaluator.color_terms(board, color));
+        trace.add("Material", white_pieces.0, black_pieces.0);
+        trace.add("Piece squares", white_pieces.1, black_pieces.1);
+
+        let [white_pawns, black_pawns] = [Color::WHITE, Color::BLACK]
+            .map(|color| pawn_structure_score(&pawns, color, &weights.pawn_structure));
+        trace.add("Pawns", white_pawns, black_pawns);
+
+        let [white_king, black_king] = [Color::WHITE, Color::BLACK]
+            .map(|color| king_safety_score(board, &pawns, color, &weights.king_safety));
+        trace.add("King safety", white_king, black_king);
+
+        let [white_activity, black_activity] = [Color::WHITE, Color::BLACK]
+            .map(|color| piece_activity_terms(board, &pawns, color, &weights.piece_activity));
+        trace.add("Mobility", white_activity.0, black_activity.0);
+        trace.add("Pieces", white_activity.1, black_activity.1);
+
+        Some(trace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::converter::Converter;
+
+    #[test]
+    fn evaluation_does_not_depend_on_pawn_hash_table() {
+        let with_table = ClassicalEvaluator::new();
+        let without_table = ClassicalEvaluator::without_pawn_hash_table(EvaluationWeights::default());
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 3 3",
+            "8/5pk1/6p1/3P4/1p6/8/5PPP/6K1 w - - 0 1",
+        ] {
+            let board = Converter::convert_string_to_board(fen.to_string());
+            // the second evaluation with the table is read from it
+            assert_eq!(with_table.evaluate(&board), without_table.evaluate(&board), "{}", fen);
+            assert_eq!(with_table.evaluate(&board), without_table.evaluate(&board), "{}", fen);
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use log::{info, warn};
+use crate::datamodel::board::{Board, FieldChange};
+use crate::datamodel::enums::piece_type::PieceType;
+use crate::datamodel::options::Options;
+use crate::evaluation::classical_evaluator::ClassicalEvaluator;
+use crate::evaluation::evaluation_trace::EvaluationTrace;
+use crate::evaluation::evaluation_weights::EvaluationWeights;
+use crate::evaluation::Evaluator;
+use crate::evaluation::nnue::{Network, NnueEvaluator};
+
+/// An implementation of Evaluator that rates positions with the network of the EvalFile option,
+/// or with the classical evaluator and the weights of the EvalParams option if no network is loaded.
+#[derive(Debug)]
+pub struct ConfiguredEvaluator {
+    classical_evaluator: ClassicalEvaluator,
+    nnue_evaluator: Option<NnueEvaluator>,
+    /// The network file the evaluator was configured with
+    eval_file: String,
+    /// The evaluation parameters file the classical evaluator was configured with
+    eval_params: String,
+}
+
+impl ConfiguredEvaluator {
+    pub fn new() -> ConfiguredEvaluator {
+        ConfiguredEvaluator {
+            classical_evaluator: ClassicalEvaluator::new(),
+            nnue_evaluator: None,
+            eval_file: String::new(),
+            eval_params: String::new(),
+        }
+    }
+
+    fn active(&self) -> &dyn Evaluator {
+        match &self.nnue_evaluator {
+            Some(nnue_evaluator) => nnue_evaluator,
+            None => &self.classical_evaluator,
+        }
+    }
+}
+
+impl Evaluator for ConfiguredEvaluator {
+    fn evaluate(&self, board: &Board) -> i32 {
+        self.active().evaluate(board)
+    }
+
+    fn configure(&mut self, options: &Options) {
+        if options.eval_params() != self.eval_params {
+            self.eval_params = options.eval_params().to_string();
+            self.classical_evaluator = ClassicalEvaluator::with_weights(EvaluationWeights::from_eval_params(&self.eval_params));
+        }
+
+        if options.eval_file() == self.eval_file {
+            return;
+        }
+        self.eval_file = options.eval_file().to_string();
+
+        if self.eval_file.is_empty() {
+            info!("No network configured, using the classical evaluation");
+            self.nnue_evaluator = None;
+            return;
+        }
+        match Network::load(&self.eval_file) {
+            Ok(network) => {
+                info!("Loaded network [{:?}] from [{}]", network, self.eval_file);
+                self.nnue_evaluator = Some(NnueEvaluator::new(network));
+            }
+            Err(error) => {
+                warn!("The network [{}] could not be loaded, using the classical evaluation: {}", self.eval_file, error);
+                println!("info string the network {} could not be loaded: {}", self.eval_file, error);
+                self.nnue_evaluator = None;
+            }
+        }
+    }
+
+    fn start_search(&self, board: &Board) {
+        self.active().start_search(board)
+    }
+
+    fn move_played(&self, changes: &[FieldChange]) {
+        self.active().move_played(changes)
+    }
+
+    fn move_undone(&self) {
+        self.active().move_undone()
+    }
+
+    fn finish_search(&self) {
+        self.active().finish_search()
+    }
+
+    // the network has no piece values, so the moves are ordered by the weights of the EvalParams option
+    fn piece_value(&self, piece_type: PieceType) -> i32 {
+        self.classical_evaluator.piece_value(piece_type)
+    }
+
+    fn trace(&self, board: &Board) -> Option<EvaluationTrace> {
+        self.active().trace(board)
+    }
+}
@@ -0,0 +1,61 @@
+use std::fmt::{Display, Formatter};
+use crate::evaluation::piece_square_evaluator::MAX_PHASE;
+use crate::evaluation::score::Score;
+
+/// A term of the evaluation with the score of each player.
+#[derive(Debug, Clone)]
+pub struct TracedTerm {
+    pub name: &'static str,
+    pub white: Score,
+    pub black: Score,
+}
+
+/// The evaluation of a position split into its terms, so it can be checked why the engine prefers a move.
+#[derive(Debug, Clone)]
+pub struct EvaluationTrace {
+    pub terms: Vec<TracedTerm>,
+    pub phase: i32,
+}
+
+impl EvaluationTrace {
+    pub fn new(phase: i32) -> EvaluationTrace {
+        EvaluationTrace {
+            terms: Vec::new(),
+            phase,
+        }
+    }
+
+    pub fn add(&mut self, name: &'static str, white: Score, black: Score) {
+        self.terms.push(TracedTerm { name, white, black });
+    }
+
+    /// Returns the sum of all terms from the view of white, blended by the game phase.
+    pub fn total(&self) -> i32 {
+        self.terms.iter()
+            .fold(Score::ZERO, |sum, term| sum + term.white - term.black)
+            .taper(self.phase)
+    }
+}
+
+/// Prints the terms as a table in centipawns, every term for the midgame and the endgame.
+impl Display for EvaluationTrace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let separator = "---------------+-------------+-------------+-------------";
+        writeln!(f, "          Term |    White    |    Black    |    Total")?;
+        writeln!(f, "               |   MG    EG  |   MG    EG  |   MG    EG")?;
+        writeln!(f, "{}", separator)?;
+        for term in &self.terms {
+            let total = term.white - term.black;
+            writeln!(f, "{:>14} | {:>5} {:>5} | {:>5} {:>5} | {:>5} {:>5}",
+                     term.name, term.white.midgame, term.white.endgame,
+                     term.black.midgame, term.black.endgame, total.midgame, total.endgame)?;
+        }
+        writeln!(f, "{}", separator)?;
+
+        let sum = self.terms.iter().fold(Score::ZERO, |sum, term| sum + term.white - term.black);
+        writeln!(f, "{:>14} |             |             | {:>5} {:>5}", "Total", sum.midgame, sum.endgame)?;
+        writeln!(f)?;
+        writeln!(f, "Game phase: {} of {}", self.phase.min(MAX_PHASE), MAX_PHASE)?;
+        write!(f, "Total evaluation: {} cp from the view of white", self.total())
+    }
+}
@@ -0,0 +1,175 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::evaluation::king_safety::KingSafetyWeights;
+use crate::evaluation::pawn_structure::PawnStructureWeights;
+use crate::evaluation::piece_activity::PieceActivityWeights;
+use crate::evaluation::piece_square_evaluator::PieceSquareWeights;
+
+/// The weights of all terms of the evaluators.
+///
+/// The values of the pieces are the values of the piece square weights. The material evaluator
+/// and the move ordering of the searches use their midgame values, so a tuned value changes all of them.
+/// The built in weights are the defaults. A weights file only needs to contain the weights
+/// it changes, all other weights keep their defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EvaluationWeights {
+    pub piece_square: PieceSquareWeights,
+    pub pawn_structure: PawnStructureWeights,
+    pub king_safety: KingSafetyWeights,
+    pub piece_activity: PieceActivityWeights,
+}
+
+impl EvaluationWeights {
+    /// Reads the weights from a toml file, or from a json file if the file does not end with .toml.
+    /// Unknown weights are rejected, so a misspelled weight does not go unnoticed.
+    pub fn load(path: &str) -> io::Result<EvaluationWeights> {
+        let content = fs::read_to_string(path)?;
+        let changes: Value = if is_toml(path) {
+            toml::from_str(&content).map_err(invalid_data)?
+        } else {
+            serde_json::from_str(&content).map_err(invalid_data)?
+        };
+
+        let mut weights = serde_json::to_value(EvaluationWeights::default()).map_err(invalid_data)?;
+        merge(&mut weights, changes);
+        serde_json::from_value(weights).map_err(invalid_data)
+    }
+
+    /// Writes all weights to a toml file, or to a json file if the path does not end with .toml.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let content = if is_toml(path) {
+            toml::to_string(self).map_err(invalid_data)?
+        } else {
+            serde_json::to_string_pretty(self).map_err(invalid_data)?
+        };
+        fs::write(path, content)
+    }
+
+    /// Returns the weights of the EvalParams option: the built in weights if no file is given
+    /// or the file could not be loaded.
+    pub fn from_eval_params(eval_params: &str) -> EvaluationWeights {
+        if eval_params.is_empty() {
+            info!("No evaluation parameters configured, using the built in weights");
+            return EvaluationWeights::default();
+        }
+        match EvaluationWeights::load(eval_params) {
+            Ok(weights) => {
+                info!("Loaded evaluation parameters from [{}]", eval_params);
+                weights
+            }
+            Err(error) => {
+                warn!("The evaluation parameters [{}] could not be loaded, using the built in weights: {}", eval_params, error);
+                println!("info string the evaluation parameters {} could not be loaded: {}", eval_params, error);
+                EvaluationWeights::default()
+            }
+        }
+    }
+}
+
+fn is_toml(path: &str) -> bool {
+    Path::new(path).extension().is_some_and(|extension| extension.eq_ignore_ascii_case("toml"))
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Replaces the values of the base by the given changes. Objects are merged key by key,
+/// so the changes may leave out any value.
+fn merge(base: &mut Value, changes: Value) {
+    match (base, changes) {
+        (Value::Object(base), Value::Object(changes)) => {
+            for (key, change) in changes {
+                match base.get_mut(&key) {
+                    Some(value) => merge(value, change),
+                    None => {
+                        base.insert(key, change);
+                    }
+                }
+            }
+        }
+        (base, change) => *base = change,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the path of a weights file in the temporary directory with the given content.
+    fn weights_file(name: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(format!("flengine-weights-{}-{}", std::process::id(), name));
+        fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn changed_weights() -> EvaluationWeights {
+        let mut weights = EvaluationWeights::default();
+        weights.piece_square.knight.value.midgame += 17;
+        weights.piece_square.pawn.endgame_table[1][2] -= 5;
+        weights.pawn_structure.doubled_pawn.endgame += 3;
+        weights
+    }
+
+    fn assert_round_trip(name: &str) {
+        let path = weights_file(name, "");
+        changed_weights().save(&path).unwrap();
+        let loaded = EvaluationWeights::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), changed_weights());
+    }
+
+    #[test]
+    fn toml_weights_survive_a_round_trip() {
+        assert_round_trip("round-trip.toml");
+    }
+
+    #[test]
+    fn json_weights_survive_a_round_trip() {
+        assert_round_trip("round-trip.json");
+    }
+
+    #[test]
+    fn partial_files_keep_the_defaults() {
+        let toml = weights_file("partial.toml", "[piece_square.knight.value]\nmidgame = 350\n\n[pawn_structure.doubled_pawn]\nendgame = -40\n");
+        let json = weights_file("partial.json", r#"{"piece_square": {"knight": {"value": {"midgame": 350}}}, "pawn_structure": {"doubled_pawn": {"endgame": -40}}}"#);
+        let mut expected = EvaluationWeights::default();
+        expected.piece_square.knight.value.midgame = 350;
+        expected.pawn_structure.doubled_pawn.endgame = -40;
+
+        for path in [toml, json] {
+            let loaded = EvaluationWeights::load(&path);
+            fs::remove_file(&path).unwrap();
+            assert_eq!(loaded.unwrap(), expected, "{}", path);
+        }
+    }
+
+    #[test]
+    fn invalid_files_are_rejected() {
+        let files = [
+            // toml content is read as json without the toml extension
+            weights_file("toml-content.yaml", "[piece_square.knight.value]\nmidgame = 350\n"),
+            weights_file("malformed.toml", "[piece_square.knight.value\nmidgame = 350\n"),
+            weights_file("malformed.json", r#"{"piece_square": {"knight": "#),
+            weights_file("unknown.json", r#"{"piece_square": {"knigth": {"value": {"midgame": 350}}}}"#),
+            weights_file("wrong-type.toml", "[piece_square.knight.value]\nmidgame = \"many\"\n"),
+        ];
+        for path in files {
+            let loaded = EvaluationWeights::load(&path);
+            fs::remove_file(&path).unwrap();
+            assert_eq!(loaded.unwrap_err().kind(), io::ErrorKind::InvalidData, "{}", path);
+        }
+        assert!(EvaluationWeights::load("/nonexistent/weights.toml").is_err());
+    }
+
+    #[test]
+    fn missing_file_falls_back_to_the_defaults() {
+        assert_eq!(EvaluationWeights::from_eval_params("/nonexistent/weights.toml"), EvaluationWeights::default());
+        assert_eq!(EvaluationWeights::from_eval_params(""), EvaluationWeights::default());
+    }
+}
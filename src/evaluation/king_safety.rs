@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use crate::datamodel::board::Board;
+use crate::datamodel::enums::color::Color;
+use crate::datamodel::enums::file::File;
+use crate::datamodel::enums::piece_type::PieceType;
+use crate::datamodel::enums::rank::Rank;
+use crate::datamodel::field::Field;
+use crate::evaluation::pawn_structure::Pawns;
+use crate::evaluation::score::Score;
+use crate::rules::piece_rule::get_covering_pieces;
+
+/// Weights of the king safety terms.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KingSafetyWeights {
+    /// Bonus of a pawn of the shield directly in front of the king
+    pub shield_pawn_near: Score,
+    /// Bonus of a pawn of the shield two ranks in front of the king
+    pub shield_pawn_far: Score,
+    /// Penalty of a file in front of the king without a pawn of the shield
+    pub shield_pawn_missing: Score,
+    /// Penalty of an opponent pawn in front of the king by its distance to the king's rank
+    pub pawn_storm: [Score; 8],
+    /// Penalty of a file next to the king without own pawns
+    pub semi_open_file: Score,
+    /// Penalty of a file next to the king without any pawns
+    pub open_file: Score,
+    /// Attack units of every attack on the king zone by piece type, indexed by PieceType::to_index
+    pub attacker_weights: [i32; 6],
+    /// Penalty per squared attack unit, divided by ATTACK_SCALE_DIVISOR
+    pub king_attack: Score,
+    /// Attack units beyond this are not counted, so the penalty does not outgrow the material
+    pub max_attack_units: i32,
+}
+
+/// Divisor of the squared attack units
+const ATTACK_SCALE_DIVISOR: i32 = 16;
+
+impl Default for KingSafetyWeights {
+    fn default() -> Self {
+        KingSafetyWeights {
+            shield_pawn_near: Score::new(12, 0),
+            shield_pawn_far: Score::new(6, 0),
+            shield_pawn_missing: Score::new(-15, 0),
+            pawn_storm: [
+                Score::new(0, 0),
+                Score::new(-30, 0),
+                Score::new(-20, 0),
+                Score::new(-10, 0),
+                Score::new(-5, 0),
+                Score::new(0, 0),
+                Score::new(0, 0),
+                Score::new(0, 0),
+            ],
+            semi_open_file: Score::new(-15, 0),
+            open_file: Score::new(-25, 0),
+            attacker_weights: [1, 3, 2, 2, 5, 0],
+            king_attack: Score::new(-10, -2),
+            max_attack_units: 40,
+        }
+    }
+}
+
+/// Rates the safety of the king of the given color.
+pub fn king_safety_score(board: &Board, pawns: &Pawns, color: Color, weights: &KingSafetyWeights) -> Score {
+    let Some((king_rank, king_file)) = find_king(board, color) else {
+        return Score::ZERO;
+    };
+    let own_pawns = pawns.of(color);
+    let opponent_pawns = pawns.of(!color);
+    let forward: isize = if color == Color::WHITE { 1 } else { -1 };
+
+    let mut score = Score::ZERO;
+
+    let min_file = king_file.saturating_sub(1);
+    let max_file = (king_file + 1).min(7);
+    for file in min_file..=max_file {
+        // pawn shield
+        let near = king_rank as isize + forward;
+        let far = king_rank as isize + 2 * forward;
+        if has_pawn(own_pawns, near, file) {
+            score += weights.shield_pawn_near;
+        } else if has_pawn(own_pawns, far, file) {
+            score += weights.shield_pawn_far;
+        } else {
+            score += weights.shield_pawn_missing;
+        }
+
+        // pawn storm: the closest opponent pawn in front of the king on this file
+        let storm_distance = (1..8).find(|distance| has_pawn(opponent_pawns, king_rank as isize + distance * forward, file));
+        if let Some(distance) = storm_distance {
+            score += weights.pawn_storm[distance as usize];
+        }
+
+        // open files give the opponent rooks and queens a way to the king
+        let file_mask = 0x0101_0101_0101_0101u64 << file;
+        if (own_pawns | opponent_pawns) & file_mask == 0 {
+            score += weights.open_file;
+        } else if own_pawns & file_mask == 0 {
+            score += weights.semi_open_file;
+        }
+    }
+
+    // attacks of opponent pieces on the fields around the king and in front of it
+    let mut attack_units = 0;
+    for (rank, file) in king_zone(king_rank, king_file, forward) {
+        let field = Field::new(File::from_index(file), Rank::from_index(rank));
+        attack_units += get_covering_pieces(board, &field, !color).iter()
+            .map(|piece_type| weights.attacker_weights[piece_type.to_index()])
+            .sum::<i32>();
+    }
+    let attack_units = attack_units.min(weights.max_attack_units);
+    score += weights.king_attack * (attack_units * attack_units) / ATTACK_SCALE_DIVISOR;
+
+    score
+}
+
+/// Returns the rank and file index of the king of the given color.
+fn find_king(board: &Board, color: Color) -> Option<(usize, usize)> {
+    (0..8).flat_map(|rank| (0..8).map(move |file| (rank, file)))
+        .find(|(rank, file)| board.get_piece(&Field::new(File::from_index(*file), Rank::from_index(*rank)))
+            .is_some_and(|piece| piece.color() == color && piece.piece_type() == PieceType::KING))
+}
+
+/// Returns the fields around the king and the three fields two ranks in front of it.
+fn king_zone(king_rank: usize, king_file: usize, forward: isize) -> Vec<(usize, usize)> {
+    let mut zone = Vec::new();
+    for rank_offset in [-1, 0, 1, 2 * forward] {
+        for file_offset in -1..=1 {
+            let rank = king_rank as isize + rank_offset;
+            let file = king_file as isize + file_offset;
+            if (0..8).contains(&rank) && (0..8).contains(&file) {
+                zone.push((rank as usize, file as usize));
+            }
+        }
+    }
+    zone
+}
+
+fn has_pawn(pawns: u64, rank: isize, file: usize) -> bool {
+    (0..8).contains(&rank) && pawns & (1 << (rank as usize * 8 + file)) != 0
+}
@@ -0,0 +1,67 @@
+use crate::datamodel::board::Board;
+use crate::datamodel::enums::file::File;
+use crate::datamodel::enums::piece_type::PieceType;
+use crate::datamodel::enums::rank::Rank;
+use crate::datamodel::field::Field;
+use crate::datamodel::options::Options;
+use crate::evaluation::evaluation_weights::EvaluationWeights;
+use crate::evaluation::piece_square_evaluator::PieceSquareWeights;
+use crate::evaluation::Evaluator;
+use crate::rules::piece_rule::PieceRule;
+
+/// Penalty of the player to move for being in check
+const CHECK_PENALTY: i32 = 100;
+
+/// An implementation of Evaluator that only counts the material on the board.
+/// The pieces have the midgame values of the piece square weights.
+#[derive(Debug, Default)]
+pub struct MaterialEvaluator {
+    weights: PieceSquareWeights,
+    /// The evaluation parameters file the evaluator was configured with
+    eval_params: String,
+}
+
+impl MaterialEvaluator {
+    pub fn new() -> MaterialEvaluator {
+        MaterialEvaluator::default()
+    }
+}
+
+impl Evaluator for MaterialEvaluator {
+    fn evaluate(&self, board: &Board) -> i32 {
+        let color = board.next_color();
+        let mut rating: i32 = 0;
+
+        for rank in 0..8 {
+            for file in 0..8 {
+                if let Some(piece) = board.get_piece(&Field::new(File::from_index(file), Rank::from_index(rank))) {
+                    // own pieces are good, opponents pieces are bad
+                    if piece.color() == color {
+                        rating += self.weights.piece_value(piece.piece_type());
+                    } else {
+                        rating -= self.weights.piece_value(piece.piece_type());
+                    }
+                }
+            }
+        }
+
+        // being in check is bad
+        if PieceRule::is_checked(board, &color) {
+            rating -= CHECK_PENALTY;
+        }
+
+        rating
+    }
+
+    fn piece_value(&self, piece_type: PieceType) -> i32 {
+        self.weights.piece_value(piece_type)
+    }
+
+    fn configure(&mut self, options: &Options) {
+        if options.eval_params() == self.eval_params {
+            return;
+        }
+        self.eval_params = options.eval_params().to_string();
+        self.weights = EvaluationWeights::from_eval_params(&self.eval_params).piece_square;
+    }
+}
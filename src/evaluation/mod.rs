@@ -0,0 +1,88 @@
+use std::fmt::Debug;
+use crate::datamodel::board::{Board, FieldChange};
+use crate::datamodel::enums::piece_type::PieceType;
+use crate::datamodel::options::Options;
+use crate::evaluation::evaluation_trace::EvaluationTrace;
+use crate::evaluation::piece_square_evaluator::DEFAULT_PIECE_SQUARE_WEIGHTS;
+
+pub mod classical_evaluator;
+pub mod configured_evaluator;
+pub mod evaluation_trace;
+pub mod evaluation_weights;
+pub mod king_safety;
+pub mod material_evaluator;
+pub mod nnue;
+pub mod pawn_structure;
+pub mod piece_activity;
+pub mod piece_square_evaluator;
+pub mod score;
+
+/// Rates positions for the search.
+///
+/// Evaluators that update their state incrementally follow the search through the
+/// start_search, move_played, move_undone and finish_search calls. The other evaluators ignore them.
+pub trait Evaluator: Debug + Send + Sync {
+    /// Returns the rating of the position in centipawns from the view of the player to move.
+    /// Positive ratings are good for the player to move.
+    fn evaluate(&self, board: &Board) -> i32;
+
+    /// Adapts the evaluator to the options of the next search.
+    fn configure(&mut self, _options: &Options) {}
+
+    /// Returns the value of a piece of the given type in centipawns, which the move ordering uses.
+    fn piece_value(&self, piece_type: PieceType) -> i32 {
+        DEFAULT_PIECE_SQUARE_WEIGHTS.piece_value(piece_type)
+    }
+
+    /// The searching thread starts to search the given position.
+    fn start_search(&self, _board: &Board) {}
+
+    /// The searching thread played a move that changed the given fields.
+    fn move_played(&self, _changes: &[FieldChange]) {}
+
+    /// The searching thread returned to the position before the last played move.
+    fn move_undone(&self) {}
+
+    /// The searching thread finished its search.
+    fn finish_search(&self) {}
+
+    /// Returns the evaluation split into its terms, or None if the evaluator has no terms.
+    fn trace(&self, _board: &Board) -> Option<EvaluationTrace> {
+        None
+    }
+}
+
+// allows to choose the evaluator at runtime
+impl<E: Evaluator + ?Sized> Evaluator for Box<E> {
+    fn evaluate(&self, board: &Board) -> i32 {
+        self.as_ref().evaluate(board)
+    }
+
+    fn configure(&mut self, options: &Options) {
+        self.as_mut().configure(options)
+    }
+
+    fn piece_value(&self, piece_type: PieceType) -> i32 {
+        self.as_ref().piece_value(piece_type)
+    }
+
+    fn start_search(&self, board: &Board) {
+        self.as_ref().start_search(board)
+    }
+
+    fn move_played(&self, changes: &[FieldChange]) {
+        self.as_ref().move_played(changes)
+    }
+
+    fn move_undone(&self) {
+        self.as_ref().move_undone()
+    }
+
+    fn finish_search(&self) {
+        self.as_ref().finish_search()
+    }
+
+    fn trace(&self, board: &Board) -> Option<EvaluationTrace> {
+        self.as_ref().trace(board)
+    }
+}
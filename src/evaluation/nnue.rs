@@ -0,0 +1,444 @@
+//! A small neural network evaluator in the style of NNUE, running on the CPU with integer arithmetic.
+//!
+//! The network has 768 inputs, one for every piece type of every color on every field.
+//! The inputs are seen from both perspectives: for black the board is mirrored vertically and
+//! the colors are swapped, so "own" pieces are always the pieces of the perspective.
+//! Both perspectives share one hidden layer, their activations are concatenated with the player
+//! to move first and feed a single output neuron.
+//!
+//! Because only a few inputs change per move, the hidden layer (the accumulator) is updated
+//! incrementally while the search plays moves instead of being computed from scratch.
+//!
+//! # File format
+//!
+//! All numbers are little endian.
+//!
+//! | Content          | Type  | Count             |
+//! |------------------|-------|-------------------|
+//! | magic `FLNN`     | bytes | 4                 |
+//! | version, 1       | u32   | 1                 |
+//! | hidden size `H`  | u32   | 1                 |
+//! | feature weights  | i16   | 768 * H           |
+//! | feature biases   | i16   | H                 |
+//! | output weights   | i16   | 2 * H             |
+//! | output bias      | i32   | 1                 |
+//!
+//! The feature weights are stored feature by feature, each with H weights.
+//! The index of a feature is `side * 384 + piece * 64 + field` with side 0 for own and 1 for
+//! opponent pieces, piece 0 to 5 for pawn, knight, bishop, rook, queen and king and field 0 to 63
+//! for a1, b1, ..., h8 as seen from the perspective.
+//! The first H output weights belong to the player to move, the other H to the opponent.
+//!
+//! The feature weights and biases are quantized by QA = 255, the output weights by QB = 64.
+//! The hidden layer uses a clipped relu to 0..=QA. The output is scaled by 400 to centipawns:
+//! `rating = (sum(activation * output weight) + output bias) * 400 / (QA * QB)`.
+
+use std::cell::RefCell;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use crate::datamodel::board::{Board, FieldChange};
+use crate::datamodel::enums::color::Color;
+use crate::datamodel::enums::file::File;
+use crate::datamodel::enums::piece_type::PieceType;
+use crate::datamodel::enums::rank::Rank;
+use crate::datamodel::field::Field;
+use crate::datamodel::piece::Piece;
+use crate::evaluation::Evaluator;
+
+const MAGIC: &[u8; 4] = b"FLNN";
+const VERSION: u32 = 1;
+const INPUT_SIZE: usize = 768;
+/// Largest supported hidden layer, which protects against broken files
+const MAX_HIDDEN_SIZE: usize = 4096;
+const QA: i64 = 255;
+const QB: i64 = 64;
+const OUTPUT_SCALE: i64 = 400;
+
+/// The weights of a network loaded from a file.
+#[derive(Clone)]
+pub struct Network {
+    hidden_size: usize,
+    feature_weights: Vec<i16>,
+    feature_biases: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i32,
+}
+
+impl Network {
+    /// Loads a network in the documented file format.
+    pub fn load(path: &str) -> std::io::Result<Network> {
+        Network::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> std::io::Result<Network> {
+        let mut reader = ByteReader { bytes, position: 0 };
+
+        if reader.take(4)? != MAGIC {
+            return Err(invalid_data("the file is no network file"));
+        }
+        let version = reader.read_u32()?;
+        if version != VERSION {
+            return Err(invalid_data(&format!("the network version {} is not supported", version)));
+        }
+        let hidden_size = reader.read_u32()? as usize;
+        if hidden_size == 0 || hidden_size > MAX_HIDDEN_SIZE {
+            return Err(invalid_data(&format!("the hidden size {} is not supported", hidden_size)));
+        }
+
+        let network = Network {
+            hidden_size,
+            feature_weights: reader.read_i16s(INPUT_SIZE * hidden_size)?,
+            feature_biases: reader.read_i16s(hidden_size)?,
+            output_weights: reader.read_i16s(2 * hidden_size)?,
+            output_bias: reader.read_u32()? as i32,
+        };
+
+        if reader.position != bytes.len() {
+            return Err(invalid_data("the file is longer than the network"));
+        }
+        Ok(network)
+    }
+
+    fn feature_weights(&self, feature: usize) -> &[i16] {
+        &self.feature_weights[feature * self.hidden_size..(feature + 1) * self.hidden_size]
+    }
+}
+
+impl std::fmt::Debug for Network {
+    // the weights are far too many to be logged
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Network")
+            .field("hidden_size", &self.hidden_size)
+            .finish()
+    }
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl ByteReader<'_> {
+    fn take(&mut self, count: usize) -> std::io::Result<&[u8]> {
+        let end = self.position + count;
+        if end > self.bytes.len() {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "the network file is truncated"));
+        }
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn read_u32(&mut self) -> std::io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn read_i16s(&mut self, count: usize) -> std::io::Result<Vec<i16>> {
+        Ok(self.take(2 * count)?
+            .chunks_exact(2)
+            .map(|chunk| i16::from_le_bytes([chunk[0], chunk[1]]))
+            .collect())
+    }
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+/// The hidden layer before the activation, from the perspective of white and of black.
+#[derive(Clone, Debug)]
+pub struct Accumulator {
+    white: Vec<i16>,
+    black: Vec<i16>,
+}
+
+impl Accumulator {
+    /// Computes the accumulator of the position from scratch.
+    pub fn new(network: &Network, board: &Board) -> Accumulator {
+        let mut accumulator = Accumulator {
+            white: network.feature_biases.clone(),
+            black: network.feature_biases.clone(),
+        };
+        for rank in 0..8 {
+            for file in 0..8 {
+                let field = Field::new(File::from_index(file), Rank::from_index(rank));
+                if let Some(piece) = board.get_piece(&field) {
+                    accumulator.add_piece(network, piece, &field);
+                }
+            }
+        }
+        accumulator
+    }
+
+    /// Updates the accumulator by the fields a move changed.
+    pub fn apply(&mut self, network: &Network, changes: &[FieldChange]) {
+        for change in changes {
+            if let Some(piece) = &change.removed {
+                self.remove_piece(network, piece, &change.field);
+            }
+            if let Some(piece) = &change.added {
+                self.add_piece(network, piece, &change.field);
+            }
+        }
+    }
+
+    fn add_piece(&mut self, network: &Network, piece: &Piece, field: &Field) {
+        let (white_feature, black_feature) = feature_indices(piece, field);
+        add_weights(&mut self.white, network.feature_weights(white_feature));
+        add_weights(&mut self.black, network.feature_weights(black_feature));
+    }
+
+    fn remove_piece(&mut self, network: &Network, piece: &Piece, field: &Field) {
+        let (white_feature, black_feature) = feature_indices(piece, field);
+        subtract_weights(&mut self.white, network.feature_weights(white_feature));
+        subtract_weights(&mut self.black, network.feature_weights(black_feature));
+    }
+
+    /// Computes the output of the network in centipawns from the view of the given color.
+    pub fn evaluate(&self, network: &Network, color: Color) -> i32 {
+        let (own, opponent) = match color {
+            Color::WHITE => (&self.white, &self.black),
+            Color::BLACK => (&self.black, &self.white),
+        };
+        let (own_weights, opponent_weights) = network.output_weights.split_at(network.hidden_size);
+
+        let output: i64 = activate(own, own_weights) + activate(opponent, opponent_weights) + network.output_bias as i64;
+        (output * OUTPUT_SCALE / (QA * QB)) as i32
+    }
+}
+
+fn add_weights(accumulator: &mut [i16], weights: &[i16]) {
+    accumulator.iter_mut().zip(weights).for_each(|(value, weight)| *value = value.wrapping_add(*weight));
+}
+
+fn subtract_weights(accumulator: &mut [i16], weights: &[i16]) {
+    accumulator.iter_mut().zip(weights).for_each(|(value, weight)| *value = value.wrapping_sub(*weight));
+}
+
+/// Applies the clipped relu to the hidden layer and multiplies it with the output weights.
+fn activate(hidden: &[i16], weights: &[i16]) -> i64 {
+    hidden.iter().zip(weights)
+        .map(|(value, weight)| (*value as i64).clamp(0, QA) * *weight as i64)
+        .sum()
+}
+
+/// Returns the input index of the piece on the field from the perspective of white and of black.
+fn feature_indices(piece: &Piece, field: &Field) -> (usize, usize) {
+    let piece_index = match piece.piece_type() {
+        PieceType::PAWN => 0,
+        PieceType::KNIGHT => 1,
+        PieceType::BISHOP => 2,
+        PieceType::ROOK => 3,
+        PieceType::QUEEN => 4,
+        PieceType::KING => 5,
+    };
+    let square = field.rank().to_index() * 8 + field.file().to_index();
+    let (white_side, black_side) = match piece.color() {
+        Color::WHITE => (0, 1),
+        Color::BLACK => (1, 0),
+    };
+
+    (
+        white_side * 384 + piece_index * 64 + square,
+        // black sees the board from the other side
+        black_side * 384 + piece_index * 64 + (square ^ 56),
+    )
+}
+
+/// The accumulators of the positions on the path from the root to the current position of the search.
+/// The entries are reused, so the search does not allocate for every move.
+#[derive(Default)]
+struct AccumulatorStack {
+    accumulators: Vec<Accumulator>,
+    len: usize,
+}
+
+thread_local! {
+    /// Every search thread follows its own path through the tree
+    static ACCUMULATOR_STACK: RefCell<AccumulatorStack> = RefCell::new(AccumulatorStack::default());
+}
+
+/// An implementation of Evaluator that rates positions with a neural network.
+#[derive(Debug)]
+pub struct NnueEvaluator {
+    network: Network,
+}
+
+impl NnueEvaluator {
+    pub fn new(network: Network) -> NnueEvaluator {
+        NnueEvaluator { network }
+    }
+}
+
+impl Evaluator for NnueEvaluator {
+    fn evaluate(&self, board: &Board) -> i32 {
+        ACCUMULATOR_STACK.with_borrow(|stack| match stack.len {
+            // outside of a search the accumulator is computed from scratch
+            0 => Accumulator::new(&self.network, board).evaluate(&self.network, board.next_color()),
+            len => stack.accumulators[len - 1].evaluate(&self.network, board.next_color()),
+        })
+    }
+
+    fn start_search(&self, board: &Board) {
+        ACCUMULATOR_STACK.with_borrow_mut(|stack| {
+            let root = Accumulator::new(&self.network, board);
+            stack.accumulators.clear();
+            stack.accumulators.push(root);
+            stack.len = 1;
+        });
+    }
+
+    fn move_played(&self, changes: &[FieldChange]) {
+        ACCUMULATOR_STACK.with_borrow_mut(|stack| {
+            if stack.len == 0 {
+                return;
+            }
+            let len = stack.len;
+            if len == stack.accumulators.len() {
+                let parent = stack.accumulators[len - 1].clone();
+                stack.accumulators.push(parent);
+            } else {
+                let (parents, children) = stack.accumulators.split_at_mut(len);
+                children[0].white.copy_from_slice(&parents[len - 1].white);
+                children[0].black.copy_from_slice(&parents[len - 1].black);
+            }
+            stack.accumulators[len].apply(&self.network, changes);
+            stack.len += 1;
+        });
+    }
+
+    fn move_undone(&self) {
+        ACCUMULATOR_STACK.with_borrow_mut(|stack| {
+            // the root stays, so the search never falls back to computing from scratch
+            if stack.len > 1 {
+                stack.len -= 1;
+            }
+        });
+    }
+
+    fn finish_search(&self) {
+        ACCUMULATOR_STACK.with_borrow_mut(|stack| stack.len = 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::converter::Converter;
+
+    const HIDDEN_SIZE: u32 = 8;
+
+    /// Returns a network file with small weights that differ for every input.
+    fn network_bytes(magic: &[u8; 4], version: u32, hidden_size: u32) -> Vec<u8> {
+        let mut state: u32 = 12345;
+        let mut next_weight = || {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            ((state >> 16) % 61) as i16 - 30
+        };
+        let hidden_size = hidden_size as usize;
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(magic);
+        bytes.extend_from_slice(&version.to_le_bytes());
+        bytes.extend_from_slice(&(hidden_size as u32).to_le_bytes());
+        for _ in 0..(INPUT_SIZE + 1 + 2) * hidden_size {
+            bytes.extend_from_slice(&next_weight().to_le_bytes());
+        }
+        bytes.extend_from_slice(&(-1234i32).to_le_bytes());
+        bytes
+    }
+
+    fn network() -> Network {
+        Network::from_bytes(&network_bytes(MAGIC, VERSION, HIDDEN_SIZE)).unwrap()
+    }
+
+    #[test]
+    fn valid_network_is_read() {
+        let network = network();
+        assert_eq!(network.hidden_size, HIDDEN_SIZE as usize);
+        assert_eq!(network.feature_weights.len(), INPUT_SIZE * HIDDEN_SIZE as usize);
+        assert_eq!(network.feature_biases.len(), HIDDEN_SIZE as usize);
+        assert_eq!(network.output_weights.len(), 2 * HIDDEN_SIZE as usize);
+        assert_eq!(network.output_bias, -1234);
+    }
+
+    #[test]
+    fn wrong_magic_or_version_is_rejected() {
+        let error = Network::from_bytes(&network_bytes(b"FLNX", VERSION, HIDDEN_SIZE)).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        let error = Network::from_bytes(&network_bytes(MAGIC, VERSION + 1, HIDDEN_SIZE)).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn unsupported_hidden_size_is_rejected() {
+        let mut bytes = network_bytes(MAGIC, VERSION, HIDDEN_SIZE);
+        bytes[8..12].copy_from_slice(&0u32.to_le_bytes());
+        assert_eq!(Network::from_bytes(&bytes).unwrap_err().kind(), ErrorKind::InvalidData);
+        // a huge size must not be allocated
+        bytes[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(Network::from_bytes(&bytes).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn truncated_network_is_rejected() {
+        let bytes = network_bytes(MAGIC, VERSION, HIDDEN_SIZE);
+        for length in (0..bytes.len()).step_by(7).chain([bytes.len() - 1]) {
+            assert!(Network::from_bytes(&bytes[..length]).is_err(), "length {}", length);
+        }
+        assert_eq!(Network::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn oversized_network_is_rejected() {
+        let mut bytes = network_bytes(MAGIC, VERSION, HIDDEN_SIZE);
+        bytes.push(0);
+        assert_eq!(Network::from_bytes(&bytes).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn incremental_update_matches_a_full_refresh() {
+        let network = network();
+        let board = Converter::convert_string_to_board("r3k2r/pPpp1ppp/8/3Pp3/8/8/PPPP1PPP/R3K2R w KQkq e6 0 1".to_string());
+        let root = Accumulator::new(&network, &board);
+
+        // a quiet move, en passant, castling, promotions with and without capture
+        for uci in ["a1b1", "d5e6", "e1g1", "e1c1", "b7a8q", "b7b8n"] {
+            let chess_move = Converter::sanitize_move(&board, &Converter::convert_string_to_move(&uci.to_string()));
+            let mut new_board = board.clone();
+            let changes = new_board.play_move_with_changes(&chess_move);
+
+            let mut accumulator = root.clone();
+            accumulator.apply(&network, &changes);
+            let refreshed = Accumulator::new(&network, &new_board);
+            assert_eq!(accumulator.white, refreshed.white, "{}", uci);
+            assert_eq!(accumulator.black, refreshed.black, "{}", uci);
+        }
+    }
+
+    #[test]
+    fn evaluator_follows_moves_and_undos() {
+        let evaluator = NnueEvaluator::new(network());
+        let board = Converter::convert_string_to_board("r3k2r/pPpp1ppp/8/3Pp3/8/8/PPPP1PPP/R3K2R w KQkq e6 0 1".to_string());
+        let full_evaluation = |board: &Board| Accumulator::new(&evaluator.network, board).evaluate(&evaluator.network, board.next_color());
+
+        evaluator.start_search(&board);
+        let mut current = board.clone();
+        let mut boards = vec![board.clone()];
+        for uci in ["d5e6", "e8c8", "b7b8q", "c8b8"] {
+            let chess_move = Converter::sanitize_move(&current, &Converter::convert_string_to_move(&uci.to_string()));
+            let changes = current.play_move_with_changes(&chess_move);
+            evaluator.move_played(&changes);
+            assert_eq!(evaluator.evaluate(&current), full_evaluation(&current), "{}", uci);
+            boards.push(current.clone());
+        }
+        for _ in 0..4 {
+            evaluator.move_undone();
+            boards.pop();
+            let board = boards.last().unwrap();
+            assert_eq!(evaluator.evaluate(board), full_evaluation(board));
+        }
+        evaluator.finish_search();
+        assert_eq!(evaluator.evaluate(&board), full_evaluation(&board));
+    }
+}
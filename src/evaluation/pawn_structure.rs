@@ -0,0 +1,229 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::datamodel::board::Board;
+use crate::datamodel::enums::color::Color;
+use crate::datamodel::enums::file::File;
+use crate::datamodel::enums::piece_type::PieceType;
+use crate::datamodel::enums::rank::Rank;
+use crate::datamodel::field::Field;
+use crate::datamodel::zobrist::Zobrist;
+use crate::evaluation::score::Score;
+
+/// Number of entries of the pawn hash table. Must be a power of two.
+const PAWN_TABLE_SIZE: usize = 1 << 16;
+
+const FILE_A: u64 = 0x0101_0101_0101_0101;
+const FILE_H: u64 = FILE_A << 7;
+
+/// Weights of the pawn structure terms.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PawnStructureWeights {
+    /// Bonus of a passed pawn by its rank seen from its own side, the first entry is the first rank
+    pub passed_pawn: [Score; 8],
+    /// Penalty of every pawn behind another pawn of the same color on the same file
+    pub doubled_pawn: Score,
+    /// Penalty of a pawn without pawns of the same color on the neighbouring files
+    pub isolated_pawn: Score,
+    /// Penalty of a pawn that cannot be defended by pawns and cannot advance safely
+    pub backward_pawn: Score,
+    /// Bonus of a pawn defended by another pawn
+    pub pawn_chain: Score,
+}
+
+impl Default for PawnStructureWeights {
+    fn default() -> Self {
+        PawnStructureWeights {
+            passed_pawn: [
+                Score::new(0, 0),
+                Score::new(5, 10),
+                Score::new(10, 17),
+                Score::new(15, 30),
+                Score::new(25, 55),
+                Score::new(45, 95),
+                Score::new(70, 150),
+                Score::new(0, 0),
+            ],
+            doubled_pawn: Score::new(-10, -25),
+            isolated_pawn: Score::new(-10, -15),
+            backward_pawn: Score::new(-8, -10),
+            pawn_chain: Score::new(8, 6),
+        }
+    }
+}
+
+/// The pawns of both colors as bitboards, bit rank * 8 + file is set if there is a pawn on the field.
+#[derive(Debug, Copy, Clone)]
+pub struct Pawns {
+    white: u64,
+    black: u64,
+}
+
+impl Pawns {
+    pub fn from_board(board: &Board) -> Pawns {
+        let mut pawns = Pawns { white: 0, black: 0 };
+        for rank in 0..8 {
+            for file in 0..8 {
+                if let Some(piece) = board.get_piece(&Field::new(File::from_index(file), Rank::from_index(rank))) {
+                    if piece.piece_type() == PieceType::PAWN {
+                        match piece.color() {
+                            Color::WHITE => pawns.white |= 1 << (rank * 8 + file),
+                            Color::BLACK => pawns.black |= 1 << (rank * 8 + file),
+                        }
+                    }
+                }
+            }
+        }
+        pawns
+    }
+
+    pub fn of(&self, color: Color) -> u64 {
+        match color {
+            Color::WHITE => self.white,
+            Color::BLACK => self.black,
+        }
+    }
+
+    /// Returns the fields attacked by the pawns of the given color.
+    pub fn attacks(&self, color: Color) -> u64 {
+        match color {
+            Color::WHITE => ((self.white << 7) & !FILE_H) | ((self.white << 9) & !FILE_A),
+            Color::BLACK => ((self.black >> 7) & !FILE_A) | ((self.black >> 9) & !FILE_H),
+        }
+    }
+}
+
+/// Rates the pawn structure of the given color.
+pub fn pawn_structure_score(pawns: &Pawns, color: Color, weights: &PawnStructureWeights) -> Score {
+    let own_pawns = pawns.of(color);
+    let opponent_pawns = pawns.of(!color);
+    let mut score = Score::ZERO;
+
+    for file in 0..8 {
+        let pawns_on_file = (own_pawns & file_mask(file)).count_ones() as i32;
+        if pawns_on_file > 1 {
+            score += weights.doubled_pawn * (pawns_on_file - 1);
+        }
+    }
+
+    let mut remaining = own_pawns;
+    while remaining != 0 {
+        let square = remaining.trailing_zeros() as usize;
+        remaining &= remaining - 1;
+        let (rank, file) = (square / 8, square % 8);
+
+        let ahead = fields_ahead(color, rank);
+        if opponent_pawns & ahead & (file_mask(file) | neighbour_files(file)) == 0 {
+            score += weights.passed_pawn[relative_rank(color, rank)];
+        }
+
+        let own_neighbours = own_pawns & neighbour_files(file);
+        if own_neighbours == 0 {
+            score += weights.isolated_pawn;
+        } else if own_neighbours & !ahead == 0 && is_stop_field_attacked(opponent_pawns, color, rank, file) {
+            // all neighbours advanced too far to defend the pawn, and it cannot catch up
+            score += weights.backward_pawn;
+        }
+
+        if own_pawns & pawn_attackers(color, rank, file) != 0 {
+            score += weights.pawn_chain;
+        }
+    }
+
+    score
+}
+
+/// Returns whether the field in front of the pawn is attacked by an opponent pawn.
+fn is_stop_field_attacked(opponent_pawns: u64, color: Color, rank: usize, file: usize) -> bool {
+    let stop_rank = match color {
+        Color::WHITE => rank + 1,
+        Color::BLACK => rank.wrapping_sub(1),
+    };
+    stop_rank < 8 && opponent_pawns & pawn_attackers(!color, stop_rank, file) != 0
+}
+
+/// Returns the fields from which pawns of the given color defend the given field.
+fn pawn_attackers(color: Color, rank: usize, file: usize) -> u64 {
+    let attacker_rank = match color {
+        Color::WHITE if rank > 0 => rank - 1,
+        Color::BLACK if rank < 7 => rank + 1,
+        _ => return 0,
+    };
+    neighbour_files(file) & (0xFF << (attacker_rank * 8))
+}
+
+/// Returns the fields on the ranks in front of the given rank, seen from the given color.
+fn fields_ahead(color: Color, rank: usize) -> u64 {
+    match color {
+        Color::WHITE if rank < 7 => !0 << ((rank + 1) * 8),
+        Color::BLACK => (1 << (rank * 8)) - 1,
+        _ => 0,
+    }
+}
+
+fn relative_rank(color: Color, rank: usize) -> usize {
+    match color {
+        Color::WHITE => rank,
+        Color::BLACK => 7 - rank,
+    }
+}
+
+fn file_mask(file: usize) -> u64 {
+    FILE_A << file
+}
+
+fn neighbour_files(file: usize) -> u64 {
+    let mut files = 0;
+    if file > 0 {
+        files |= file_mask(file - 1);
+    }
+    if file < 7 {
+        files |= file_mask(file + 1);
+    }
+    files
+}
+
+/// A cache of pawn structure scores, keyed by the zobrist hash of the pawns only.
+/// The pawns change rarely during the search, so most lookups hit.
+///
+/// The table is lockless like the transposition table, so all search threads can share it.
+pub struct PawnHashTable {
+    entries: Vec<(AtomicU64, AtomicU64)>,
+}
+
+impl PawnHashTable {
+    pub fn new() -> PawnHashTable {
+        PawnHashTable {
+            entries: (0..PAWN_TABLE_SIZE).map(|_| (AtomicU64::new(0), AtomicU64::new(0))).collect(),
+        }
+    }
+
+    /// Returns the pawn structure score from the view of white, computing it if it is not cached.
+    pub fn score(&self, board: &Board, pawns: &Pawns, weights: &PawnStructureWeights) -> Score {
+        let hash = Zobrist::pawn_hash(board);
+        let (checksum, data) = &self.entries[hash as usize & (PAWN_TABLE_SIZE - 1)];
+
+        let stored = data.load(Ordering::Relaxed);
+        if checksum.load(Ordering::Relaxed) ^ stored == hash {
+            return Score::new(stored as u32 as i32, (stored >> 32) as u32 as i32);
+        }
+
+        let score = pawn_structure_score(pawns, Color::WHITE, weights)
+            - pawn_structure_score(pawns, Color::BLACK, weights);
+
+        let encoded = (score.midgame as u32 as u64) | ((score.endgame as u32 as u64) << 32);
+        data.store(encoded, Ordering::Relaxed);
+        checksum.store(hash ^ encoded, Ordering::Relaxed);
+        score
+    }
+}
+
+impl Debug for PawnHashTable {
+    // the entries are far too many to be logged
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PawnHashTable")
+            .field("size", &self.entries.len())
+            .finish()
+    }
+}
@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+use crate::datamodel::board::Board;
+use crate::datamodel::enums::color::Color;
+use crate::datamodel::enums::file::File;
+use crate::datamodel::enums::piece_type::PieceType;
+use crate::datamodel::enums::rank::Rank;
+use crate::datamodel::field::Field;
+use crate::evaluation::pawn_structure::Pawns;
+use crate::evaluation::score::Score;
+use crate::rules::piece_rule::PieceRule;
+
+/// Weights of the mobility and piece activity terms.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PieceActivityWeights {
+    /// Bonus per safe destination field by piece type, indexed by PieceType::to_index
+    pub mobility: [Score; 6],
+    /// Number of safe destination fields a piece of the type has on average, indexed by PieceType::to_index.
+    /// Pieces with fewer fields get a penalty.
+    pub mobility_offset: [i32; 6],
+    /// Bonus of a knight on an outpost
+    pub knight_outpost: Score,
+    /// Bonus of a bishop on an outpost
+    pub bishop_outpost: Score,
+    /// Bonus of a rook on a file without pawns
+    pub rook_open_file: Score,
+    /// Bonus of a rook on a file without own pawns
+    pub rook_semi_open_file: Score,
+    /// Bonus of having both bishops
+    pub bishop_pair: Score,
+}
+
+impl Default for PieceActivityWeights {
+    fn default() -> Self {
+        PieceActivityWeights {
+            mobility: [
+                Score::new(0, 0),
+                Score::new(3, 6),
+                Score::new(5, 5),
+                Score::new(4, 4),
+                Score::new(1, 2),
+                Score::new(0, 0),
+            ],
+            mobility_offset: [0, 7, 7, 4, 14, 0],
+            knight_outpost: Score::new(25, 15),
+            bishop_outpost: Score::new(15, 8),
+            rook_open_file: Score::new(30, 10),
+            rook_semi_open_file: Score::new(15, 5),
+            bishop_pair: Score::new(30, 50),
+        }
+    }
+}
+
+/// Rates the activity of the pieces of the given color.
+pub fn piece_activity_score(board: &Board, pawns: &Pawns, color: Color, weights: &PieceActivityWeights) -> Score {
+    let (mobility, placement) = piece_activity_terms(board, pawns, color, weights);
+    mobility + placement
+}
+
+/// Rates the activity of the pieces of the given color, split into the mobility
+/// and the placement terms, which are the outposts, the rook files and the bishop pair.
+pub fn piece_activity_terms(board: &Board, pawns: &Pawns, color: Color, weights: &PieceActivityWeights) -> (Score, Score) {
+    let own_pawns = pawns.of(color);
+    let all_pawns = own_pawns | pawns.of(!color);
+    let opponent_pawn_attacks = pawns.attacks(!color);
+    let own_pawn_attacks = pawns.attacks(color);
+
+    let mut mobility = Score::ZERO;
+    let mut placement = Score::ZERO;
+    let mut bishops = 0;
+
+    for rank in 0..8 {
+        for file in 0..8 {
+            let field = Field::new(File::from_index(file), Rank::from_index(rank));
+            let Some(piece) = board.get_piece(&field).filter(|piece| piece.color() == color) else {
+                continue;
+            };
+            let piece_type = piece.piece_type();
+            if piece_type == PieceType::PAWN || piece_type == PieceType::KING {
+                continue;
+            }
+            let type_index = piece_type.to_index();
+
+            // fields attacked by opponent pawns are not safe for pieces
+            let safe_fields = PieceRule::get_moves_of_piece(board, &field).iter()
+                .map(|chess_move| chess_move.to_field())
+                .filter(|to_field| opponent_pawn_attacks & bit(to_field) == 0)
+                .count() as i32;
+            mobility += weights.mobility[type_index] * (safe_fields - weights.mobility_offset[type_index]);
+
+            let square = 1 << (rank * 8 + file);
+            match piece_type {
+                PieceType::KNIGHT | PieceType::BISHOP => {
+                    if is_outpost(pawns, color, rank, file) && own_pawn_attacks & square != 0 {
+                        placement += if piece_type == PieceType::KNIGHT { weights.knight_outpost } else { weights.bishop_outpost };
+                    }
+                    if piece_type == PieceType::BISHOP {
+                        bishops += 1;
+                    }
+                }
+                PieceType::ROOK => {
+                    let file_mask = 0x0101_0101_0101_0101u64 << file;
+                    if all_pawns & file_mask == 0 {
+                        placement += weights.rook_open_file;
+                    } else if own_pawns & file_mask == 0 {
+                        placement += weights.rook_semi_open_file;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if bishops >= 2 {
+        placement += weights.bishop_pair;
+    }
+
+    (mobility, placement)
+}
+
+/// Returns whether the field lies in the opponent half and can never be attacked by opponent pawns.
+fn is_outpost(pawns: &Pawns, color: Color, rank: usize, file: usize) -> bool {
+    let (relative_rank, fields_ahead) = match color {
+        Color::WHITE if rank < 7 => (rank, !0u64 << ((rank + 1) * 8)),
+        Color::BLACK => (7 - rank, (1u64 << (rank * 8)) - 1),
+        _ => return false,
+    };
+    if !(3..=5).contains(&relative_rank) {
+        return false;
+    }
+
+    let mut neighbour_files = 0;
+    if file > 0 {
+        neighbour_files |= 0x0101_0101_0101_0101u64 << (file - 1);
+    }
+    if file < 7 {
+        neighbour_files |= 0x0101_0101_0101_0101u64 << (file + 1);
+    }
+    pawns.of(!color) & neighbour_files & fields_ahead == 0
+}
+
+fn bit(field: &Field) -> u64 {
+    1 << (field.rank().to_index() * 8 + field.file().to_index())
+}
@@ -0,0 +1,308 @@
+use std::sync::LazyLock;
+use serde::{Deserialize, Serialize};
+use crate::datamodel::board::Board;
+use crate::datamodel::enums::color::Color;
+use crate::datamodel::enums::file::File;
+use crate::datamodel::enums::piece_type::PieceType;
+use crate::datamodel::enums::rank::Rank;
+use crate::datamodel::field::Field;
+use crate::evaluation::Evaluator;
+use crate::evaluation::score::Score;
+
+/// Game phase of the starting position. Every knight and bishop counts 1, every rook 2 and every queen 4.
+pub const MAX_PHASE: i32 = 24;
+
+/// Contribution of each piece type to the game phase, indexed by PieceType::to_index
+const PHASE_WEIGHTS: [i32; 6] = [0, 2, 1, 1, 4, 0];
+
+/// The built in weights, whose piece values are used by evaluators without own piece values
+pub static DEFAULT_PIECE_SQUARE_WEIGHTS: LazyLock<PieceSquareWeights> = LazyLock::new(PieceSquareWeights::default);
+
+// The piece square tables are given from the view of white with a8 first and h1 last,
+// so they read like a board diagram.
+
+const MIDGAME_PAWN: [[i32; 8]; 8] = [
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+    [ 98, 134,  61,  95,  68, 126,  34, -11],
+    [ -6,   7,  26,  31,  65,  56,  25, -20],
+    [-14,  13,   6,  21,  23,  12,  17, -23],
+    [-27,  -2,  -5,  12,  17,   6,  10, -25],
+    [-26,  -4,  -4, -10,   3,   3,  33, -12],
+    [-35,  -1, -20, -23, -15,  24,  38, -22],
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+];
+
+const ENDGAME_PAWN: [[i32; 8]; 8] = [
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+    [178, 173, 158, 134, 147, 132, 165, 187],
+    [ 94, 100,  85,  67,  56,  53,  82,  84],
+    [ 32,  24,  13,   5,  -2,   4,  17,  17],
+    [ 13,   9,  -3,  -7,  -7,  -8,   3,  -1],
+    [  4,   7,  -6,   1,   0,  -5,  -1,  -8],
+    [ 13,   8,   8,  10,  13,   0,   2,  -7],
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+];
+
+const MIDGAME_ROOK: [[i32; 8]; 8] = [
+    [ 32,  42,  32,  51,  63,   9,  31,  43],
+    [ 27,  32,  58,  62,  80,  67,  26,  44],
+    [ -5,  19,  26,  36,  17,  45,  61,  16],
+    [-24, -11,   7,  26,  24,  35,  -8, -20],
+    [-36, -26, -12,  -1,   9,  -7,   6, -23],
+    [-45, -25, -16, -17,   3,   0,  -5, -33],
+    [-44, -16, -20,  -9,  -1,  11,  -6, -71],
+    [-19, -13,   1,  17,  16,   7, -37, -26],
+];
+
+const ENDGAME_ROOK: [[i32; 8]; 8] = [
+    [ 13,  10,  18,  15,  12,  12,   8,   5],
+    [ 11,  13,  13,  11,  -3,   3,   8,   3],
+    [  7,   7,   7,   5,   4,  -3,  -5,  -3],
+    [  4,   3,  13,   1,   2,   1,  -1,   2],
+    [  3,   5,   8,   4,  -5,  -6,  -8, -11],
+    [ -4,   0,  -5,  -1,  -7, -12,  -8, -16],
+    [ -6,  -6,   0,   2,  -9,  -9, -11,  -3],
+    [ -9,   2,   3,  -1,  -5, -13,   4, -20],
+];
+
+const MIDGAME_BISHOP: [[i32; 8]; 8] = [
+    [-29,   4, -82, -37, -25, -42,   7,  -8],
+    [-26,  16, -18, -13,  30,  59,  18, -47],
+    [-16,  37,  43,  40,  35,  50,  37,  -2],
+    [ -4,   5,  19,  50,  37,  37,   7,  -2],
+    [ -6,  13,  13,  26,  34,  12,  10,   4],
+    [  0,  15,  15,  15,  14,  27,  18,  10],
+    [  4,  15,  16,   0,   7,  21,  33,   1],
+    [-33,  -3, -14, -21, -13, -12, -39, -21],
+];
+
+const ENDGAME_BISHOP: [[i32; 8]; 8] = [
+    [-14, -21, -11,  -8,  -7,  -9, -17, -24],
+    [ -8,  -4,   7, -12,  -3, -13,  -4, -14],
+    [  2,  -8,   0,  -1,  -2,   6,   0,   4],
+    [ -3,   9,  12,   9,  14,  10,   3,   2],
+    [ -6,   3,  13,  19,   7,  10,  -3,  -9],
+    [-12,  -3,   8,  10,  13,   3,  -7, -15],
+    [-14, -18,  -7,  -1,   4,  -9, -15, -27],
+    [-23,  -9, -23,  -5,  -9, -16,  -5, -17],
+];
+
+const MIDGAME_KNIGHT: [[i32; 8]; 8] = [
+    [-167, -89, -34, -49,  61, -97, -15, -107],
+    [ -73, -41,  72,  36,  23,  62,   7,  -17],
+    [ -47,  60,  37,  65,  84, 129,  73,   44],
+    [  -9,  17,  19,  53,  37,  69,  18,   22],
+    [ -13,   4,  16,  13,  28,  19,  21,   -8],
+    [ -23,  -9,  12,  10,  19,  17,  25,  -16],
+    [ -29, -53, -12,  -3,  -1,  18, -14,  -19],
+    [-105, -21, -58, -33, -17, -28, -19,  -23],
+];
+
+const ENDGAME_KNIGHT: [[i32; 8]; 8] = [
+    [-58, -38, -13, -28, -31, -27, -63, -99],
+    [-25,  -8, -25,  -2,  -9, -25, -24, -52],
+    [-24, -20,  10,   9,  -1,  -9, -19, -41],
+    [-17,   3,  22,  22,  22,  11,   8, -18],
+    [-18,  -6,  16,  25,  16,  17,   4, -18],
+    [-23,  -3,  -1,  15,  10,  -3, -20, -22],
+    [-42, -20, -10,  -5,  -2, -20, -23, -44],
+    [-29, -51, -23, -15, -22, -18, -50, -64],
+];
+
+const MIDGAME_QUEEN: [[i32; 8]; 8] = [
+    [-28,   0,  29,  12,  59,  44,  43,  45],
+    [-24, -39,  -5,   1, -16,  57,  28,  54],
+    [-13, -17,   7,   8,  29,  56,  47,  57],
+    [-27, -27, -16, -16,  -1,  17,  -2,   1],
+    [ -9, -26,  -9, -10,  -2,  -4,   3,  -3],
+    [-14,   2, -11,  -2,  -5,   2,  14,   5],
+    [-35,  -8,  11,   2,   8,  15,  -3,   1],
+    [ -1, -18,  -9,  10, -15, -25, -31, -50],
+];
+
+const ENDGAME_QUEEN: [[i32; 8]; 8] = [
+    [ -9,  22,  22,  27,  27,  19,  10,  20],
+    [-17,  20,  32,  41,  58,  25,  30,   0],
+    [-20,   6,   9,  49,  47,  35,  19,   9],
+    [  3,  22,  24,  45,  57,  40,  57,  36],
+    [-18,  28,  19,  47,  31,  34,  39,  23],
+    [-16, -27,  15,   6,   9,  17,  10,   5],
+    [-22, -23, -30, -16, -16, -23, -36, -32],
+    [-33, -28, -22, -43,  -5, -32, -20, -41],
+];
+
+const MIDGAME_KING: [[i32; 8]; 8] = [
+    [-65,  23,  16, -15, -56, -34,   2,  13],
+    [ 29,  -1, -20,  -7,  -8,  -4, -38, -29],
+    [ -9,  24,   2, -16, -20,   6,  22, -22],
+    [-17, -20, -12, -27, -30, -25, -14, -36],
+    [-49,  -1, -27, -39, -46, -44, -33, -51],
+    [-14, -14, -22, -46, -44, -30, -15, -27],
+    [  1,   7,  -8, -64, -43, -16,   9,   8],
+    [-15,  36,  12, -54,   8, -28,  24,  14],
+];
+
+const ENDGAME_KING: [[i32; 8]; 8] = [
+    [-74, -35, -18, -18, -11,  15,   4, -17],
+    [-12,  17,  14,  17,  17,  38,  23,  11],
+    [ 10,  17,  23,  15,  20,  45,  44,  13],
+    [ -8,  22,  24,  27,  26,  33,  26,   3],
+    [-18,  -4,  21,  24,  27,  23,   9, -11],
+    [-19,  -3,  11,  21,  23,  16,   7,  -9],
+    [-27, -11,   4,  13,  14,   4,  -5, -17],
+    [-53, -34, -21, -11, -28, -14, -24, -43],
+];
+
+/// The material value and the piece square tables of one piece type.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PieceWeights {
+    pub value: Score,
+    /// Bonus by field from the view of white with a8 first and h1 last
+    pub midgame_table: [[i32; 8]; 8],
+    pub endgame_table: [[i32; 8]; 8],
+}
+
+impl PieceWeights {
+    const fn new(midgame_value: i32, endgame_value: i32, midgame_table: [[i32; 8]; 8], endgame_table: [[i32; 8]; 8]) -> PieceWeights {
+        PieceWeights {
+            value: Score::new(midgame_value, endgame_value),
+            midgame_table,
+            endgame_table,
+        }
+    }
+}
+
+/// The material values and piece square tables of all piece types.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PieceSquareWeights {
+    pub pawn: PieceWeights,
+    pub rook: PieceWeights,
+    pub bishop: PieceWeights,
+    pub knight: PieceWeights,
+    pub queen: PieceWeights,
+    pub king: PieceWeights,
+}
+
+impl PieceSquareWeights {
+    /// Returns the midgame value of a piece of the given type in centipawns. The king has no value.
+    pub fn piece_value(&self, piece_type: PieceType) -> i32 {
+        self.of(piece_type).value.midgame
+    }
+
+    pub fn of(&self, piece_type: PieceType) -> &PieceWeights {
+        match piece_type {
+            PieceType::PAWN => &self.pawn,
+            PieceType::ROOK => &self.rook,
+            PieceType::BISHOP => &self.bishop,
+            PieceType::KNIGHT => &self.knight,
+            PieceType::QUEEN => &self.queen,
+            PieceType::KING => &self.king,
+        }
+    }
+}
+
+impl Default for PieceSquareWeights {
+    fn default() -> Self {
+        PieceSquareWeights {
+            pawn: PieceWeights::new(82, 94, MIDGAME_PAWN, ENDGAME_PAWN),
+            rook: PieceWeights::new(477, 512, MIDGAME_ROOK, ENDGAME_ROOK),
+            bishop: PieceWeights::new(365, 297, MIDGAME_BISHOP, ENDGAME_BISHOP),
+            knight: PieceWeights::new(337, 281, MIDGAME_KNIGHT, ENDGAME_KNIGHT),
+            queen: PieceWeights::new(1025, 936, MIDGAME_QUEEN, ENDGAME_QUEEN),
+            king: PieceWeights::new(0, 0, MIDGAME_KING, ENDGAME_KING),
+        }
+    }
+}
+
+/// An implementation of Evaluator that rates the material and the placement of the pieces.
+///
+/// Every piece has a value and a bonus for its field in the midgame and in the endgame.
+/// The rating blends both by the game phase, which shrinks as pieces leave the board,
+/// so for example the king is kept safe in the midgame and becomes active in the endgame.
+#[derive(Debug, Default)]
+pub struct PieceSquareEvaluator {
+    weights: PieceSquareWeights,
+}
+
+impl PieceSquareEvaluator {
+    pub fn with_weights(weights: PieceSquareWeights) -> PieceSquareEvaluator {
+        PieceSquareEvaluator { weights }
+    }
+
+    /// Returns the material and placement of the pieces from the view of white and the game phase.
+    pub fn score(&self, board: &Board) -> (Score, i32) {
+        let mut score = Score::ZERO;
+        let mut phase = 0;
+
+        for rank in 0..8 {
+            for file in 0..8 {
+                if let Some(piece) = board.get_piece(&Field::new(File::from_index(file), Rank::from_index(rank))) {
+                    let piece_weights = self.weights.of(piece.piece_type());
+                    let (row, column) = table_field(piece.color(), rank, file);
+                    let piece_score = piece_weights.value + Score::new(
+                        piece_weights.midgame_table[row][column],
+                        piece_weights.endgame_table[row][column],
+                    );
+
+                    match piece.color() {
+                        Color::WHITE => score += piece_score,
+                        Color::BLACK => score -= piece_score,
+                    }
+                    phase += PHASE_WEIGHTS[piece.piece_type().to_index()];
+                }
+            }
+        }
+
+        (score, phase)
+    }
+
+    /// Returns the material and the placement of the pieces of the given color separately.
+    pub fn color_terms(&self, board: &Board, color: Color) -> (Score, Score) {
+        let mut material = Score::ZERO;
+        let mut placement = Score::ZERO;
+
+        for rank in 0..8 {
+            for file in 0..8 {
+                if let Some(piece) = board.get_piece(&Field::new(File::from_index(file), Rank::from_index(rank))).filter(|piece| piece.color() == color) {
+                    let piece_weights = self.weights.of(piece.piece_type());
+                    let (row, column) = table_field(color, rank, file);
+                    material += piece_weights.value;
+                    placement += Score::new(piece_weights.midgame_table[row][column], piece_weights.endgame_table[row][column]);
+                }
+            }
+        }
+
+        (material, placement)
+    }
+}
+
+impl Evaluator for PieceSquareEvaluator {
+    fn evaluate(&self, board: &Board) -> i32 {
+        let (score, phase) = self.score(board);
+        let rating = score.taper(phase);
+
+        if board.next_color() == Color::WHITE { rating } else { -rating }
+    }
+
+    fn piece_value(&self, piece_type: PieceType) -> i32 {
+        self.weights.piece_value(piece_type)
+    }
+}
+
+/// Blends the midgame and endgame rating by the game phase.
+/// Promotions can raise the phase beyond the one of the starting position.
+pub fn taper(midgame: i32, endgame: i32, phase: i32) -> i32 {
+    let phase = phase.min(MAX_PHASE);
+    (midgame * phase + endgame * (MAX_PHASE - phase)) / MAX_PHASE
+}
+
+/// Returns the row and column in the piece square tables of a piece of the given color on the given field.
+/// The tables are mirrored vertically for black.
+pub fn table_field(color: Color, rank: usize, file: usize) -> (usize, usize) {
+    match color {
+        Color::WHITE => (7 - rank, file),
+        Color::BLACK => (rank, file),
+    }
+}
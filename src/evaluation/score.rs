@@ -0,0 +1,71 @@
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+use serde::{Deserialize, Serialize};
+use crate::evaluation::piece_square_evaluator::taper;
+
+/// A rating in centipawns with separate values for the midgame and the endgame.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Score {
+    pub midgame: i32,
+    pub endgame: i32,
+}
+
+impl Score {
+    pub const ZERO: Score = Score::new(0, 0);
+
+    pub const fn new(midgame: i32, endgame: i32) -> Score {
+        Score { midgame, endgame }
+    }
+
+    /// Blends the midgame and endgame value by the given game phase.
+    pub fn taper(&self, phase: i32) -> i32 {
+        taper(self.midgame, self.endgame, phase)
+    }
+}
+
+impl Add for Score {
+    type Output = Score;
+    fn add(self, other: Score) -> Score {
+        Score::new(self.midgame + other.midgame, self.endgame + other.endgame)
+    }
+}
+
+impl AddAssign for Score {
+    fn add_assign(&mut self, other: Score) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Score {
+    type Output = Score;
+    fn sub(self, other: Score) -> Score {
+        Score::new(self.midgame - other.midgame, self.endgame - other.endgame)
+    }
+}
+
+impl SubAssign for Score {
+    fn sub_assign(&mut self, other: Score) {
+        *self = *self - other;
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+    fn neg(self) -> Score {
+        Score::new(-self.midgame, -self.endgame)
+    }
+}
+
+impl Mul<i32> for Score {
+    type Output = Score;
+    fn mul(self, factor: i32) -> Score {
+        Score::new(self.midgame * factor, self.endgame * factor)
+    }
+}
+
+impl Div<i32> for Score {
+    type Output = Score;
+    fn div(self, divisor: i32) -> Score {
+        Score::new(self.midgame / divisor, self.endgame / divisor)
+    }
+}
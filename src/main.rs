@@ -15,8 +15,10 @@ mod datamodel;
 mod move_provider;
 mod converter;
 mod rules;
+mod evaluation;
 mod controller;
 mod logging;
+mod tuning;
 
 #[tokio::main(flavor = "multi_thread", worker_threads = 2)]
 async fn main() {
@@ -35,5 +37,18 @@ async fn main() {
 
     log_panics::init();
 
-    RequestHandler::start_up(logging_config, handle);
+    let arguments: Vec<String> = std::env::args().collect();
+    if arguments.get(1).map(String::as_str) == Some("tune") {
+        tuning::run(&arguments[2..]);
+        return;
+    }
+
+    // evaluation weights to start with, the gui may change them with the EvalParams option
+    let eval_params = arguments.iter()
+        .position(|argument| argument == "--eval-params")
+        .and_then(|index| arguments.get(index + 1))
+        .cloned()
+        .unwrap_or_default();
+
+    RequestHandler::start_up(logging_config, handle, eval_params);
 }
@@ -1,86 +1,340 @@
 use crate::converter::converter::Converter;
 use crate::datamodel::board::Board;
-use crate::datamodel::chess_move::ChessMove;
 use crate::datamodel::options::Options;
+use crate::move_provider::http_client::HttpClient;
+use crate::move_provider::response_cache::{cache_key, ResponseCache};
 use crate::move_provider::MoveProvider;
+use crate::move_provider::scored_move::ScoredMove;
 
-use reqwest::blocking::get;
-use log::{ info, warn};
+use std::cmp::Reverse;
+use log::{debug, info, warn};
+use reqwest::Url;
 use serde::Deserialize;
 
-#[derive(Deserialize)]
+/// Base url of the Lichess tablebase
+pub const DEFAULT_TABLEBASE_URL: &str = "https://tablebase.lichess.ovh";
+
+#[derive(Deserialize, Debug)]
 struct EndgameMove {
     pub uci: String,
     pub zeroing: bool,
     pub checkmate: bool,
     pub stalemate: bool,
-    pub variant_win: bool,
-    pub variant_loss: bool,
     pub insufficient_material: bool,
+    /// Distance to the next zeroing move from the view of the opponent, if known
+    #[serde(default)]
+    pub dtz: Option<i32>,
+    /// Distance to mate from the view of the opponent, if known
+    #[serde(default)]
+    pub dtm: Option<i32>,
+    /// The result of the position after the move from the view of the opponent
     pub category: EndgameCategory,
 }
 
-#[derive(Deserialize)]
+/// The result of a position for the player to move.
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
 enum EndgameCategory {
-    #[serde(rename = "win")]
-    WIN,
-    #[serde(rename = "loss")]
-    LOSS,
+    Win,
+    /// A win by the Syzygy tables, whose distance to mate is unknown
+    SyzygyWin,
+    /// A win or a cursed win, the rounded distance of the tables does not tell
+    MaybeWin,
+    /// A win that the 50-move rule turns into a draw
+    CursedWin,
+    Draw,
+    Unknown,
+    /// A loss that the 50-move rule turns into a draw
+    BlessedLoss,
+    MaybeLoss,
+    SyzygyLoss,
+    Loss,
+}
+
+impl EndgameCategory {
+    /// Returns the category of the same position from the view of the other player.
+    fn for_opponent(self) -> EndgameCategory {
+        match self {
+            EndgameCategory::Win | EndgameCategory::SyzygyWin => EndgameCategory::Loss,
+            EndgameCategory::MaybeWin => EndgameCategory::MaybeLoss,
+            EndgameCategory::CursedWin => EndgameCategory::BlessedLoss,
+            EndgameCategory::Draw => EndgameCategory::Draw,
+            EndgameCategory::Unknown => EndgameCategory::Unknown,
+            EndgameCategory::BlessedLoss => EndgameCategory::CursedWin,
+            EndgameCategory::MaybeLoss => EndgameCategory::MaybeWin,
+            EndgameCategory::SyzygyLoss | EndgameCategory::Loss => EndgameCategory::Win,
+        }
+    }
+
+    /// Returns how good the category is, the higher the better.
+    /// An unknown result is worse than a safe draw.
+    fn rank(self) -> u8 {
+        match self {
+            EndgameCategory::Win | EndgameCategory::SyzygyWin => 8,
+            EndgameCategory::MaybeWin => 7,
+            EndgameCategory::CursedWin => 6,
+            EndgameCategory::Draw => 5,
+            EndgameCategory::Unknown => 4,
+            EndgameCategory::BlessedLoss => 3,
+            EndgameCategory::MaybeLoss => 2,
+            EndgameCategory::SyzygyLoss | EndgameCategory::Loss => 1,
+        }
+    }
+
+    /// Returns the expected result and how sure it is. The 50-move rule turns a cursed win and
+    /// a blessed loss into draws, but they keep the chance that the opponent errs.
+    fn expected_score(self) -> (f64, f64) {
+        match self {
+            EndgameCategory::Win | EndgameCategory::SyzygyWin => (1.0, 1.0),
+            EndgameCategory::MaybeWin => (1.0, 0.5),
+            EndgameCategory::CursedWin => (0.55, 1.0),
+            EndgameCategory::Draw => (0.5, 1.0),
+            EndgameCategory::Unknown => (0.5, 0.0),
+            EndgameCategory::BlessedLoss => (0.45, 1.0),
+            EndgameCategory::MaybeLoss => (0.0, 0.5),
+            EndgameCategory::SyzygyLoss | EndgameCategory::Loss => (0.0, 1.0),
+        }
+    }
 }
 
 #[derive(Deserialize)]
 struct EndgameResponse {
     pub checkmate: bool,
     pub stalemate: bool,
-    pub variant_win: bool,
-    pub variant_loss: bool,
     pub insufficient_material: bool,
     pub category: EndgameCategory,
     pub moves: Vec<EndgameMove>,
 }
 
-/// An implementation of MoveProvider that uses the Lichess Opening Explorer
-/// to find the best move in an opening situation.
+impl EndgameMove {
+    /// Returns the result of the move for the player who makes it, including the 50-move rule:
+    /// a win or loss that needs more plies to the next zeroing move than the rule leaves is a draw.
+    fn category_for_player(&self, halfmove_clock: u16) -> EndgameCategory {
+        let category = self.category.for_opponent();
+        let halfmove_clock = if self.zeroing { 0 } else { halfmove_clock as u32 + 1 };
+        let is_spoiled = self.dtz.is_some_and(|dtz| dtz.unsigned_abs() + halfmove_clock > 100);
+
+        match category {
+            EndgameCategory::Win | EndgameCategory::MaybeWin if is_spoiled => EndgameCategory::CursedWin,
+            EndgameCategory::Loss | EndgameCategory::MaybeLoss if is_spoiled => EndgameCategory::BlessedLoss,
+            category => category,
+        }
+    }
+
+    /// Returns the key that orders the moves of the same category, the higher the better:
+    /// wins as short as possible, preferring checkmate and then zeroing moves, which reset the
+    /// 50-move rule; losses as long as possible; draws that end the game right away first.
+    fn order_within_category(&self, category: EndgameCategory) -> (bool, i64) {
+        // the distances count the plies of the opponent, so they are negative if he loses
+        let distance = self.dtm.or(self.dtz).map_or(i64::MAX / 2, |distance| distance.unsigned_abs() as i64);
+        match category {
+            EndgameCategory::Win | EndgameCategory::SyzygyWin | EndgameCategory::MaybeWin | EndgameCategory::CursedWin => {
+                if self.checkmate {
+                    (true, 0)
+                } else if self.dtm.is_none() && self.zeroing {
+                    (false, i64::MAX / 4 - distance)
+                } else {
+                    (false, -distance)
+                }
+            }
+            EndgameCategory::Draw => (self.stalemate || self.insufficient_material, 0),
+            _ => (false, distance),
+        }
+    }
+}
+
+/// An implementation of MoveProvider that uses the Lichess Tablebase
+/// to play endgames with up to 7 pieces perfectly.
+///
+/// The moves are ordered by their result for the engine, then by their distances.
+/// The halfmove clock of the board decides whether the 50-move rule spoils a result.
 #[derive(Debug)]
-pub struct Endgame;
+pub struct Endgame {
+    http_client: HttpClient,
+}
+
+impl Endgame {
+    pub fn new() -> Endgame {
+        Endgame { http_client: HttpClient::new("Endgame") }
+    }
+}
 
 impl MoveProvider for Endgame{
-    fn get_recommended_moves(&mut self, board: &Board, options: Options) -> Vec<ChessMove> {
+    fn get_recommended_moves(&mut self, board: &Board, options: Options) -> Vec<ScoredMove> {
         let piece_count = board.piece_count();
         if piece_count > 7 {
             info!("The given board has more than 7 pieces left: [{}]", piece_count);
             return Vec::new();
         }
 
-        let fen_string: String = Converter::convert_board_to_string(&board);
+        let fen_string: String = Converter::convert_board_to_string(board);
 
         info!("Requesting endgame moves for: [{}]", fen_string);
 
-        let body = get("https://tablebase.lichess.ovh/standard?fen=".to_string() + fen_string.as_str());
-        match body {
-            Ok(body) => {
-                let response_string: String = match body.text() {
-                    Ok(text) => text,
-                    Err(e) => { warn!("Could not convert response to string: {}", e); return Vec::new(); }
-                };
-
-                let endgame_data: EndgameResponse = match serde_json::from_str(response_string.as_str()) {
-                    Ok(data) => data,
-                    Err(e) => { warn!("Could not parse response string: {}", e); return Vec::new(); }
-                };
-
-                info!("Received: [{} moves]", endgame_data.moves.len());
-
-                endgame_data.moves.into_iter()
-                    .map(|mov| Converter::convert_string_to_move(&mov.uci))
-                    .map(|mov| Converter::sanitize_move(&board, &mov))
-                    .collect()
+        let base_url = options.tablebase_url().trim_end_matches('/');
+        let url = match Url::parse_with_params(&format!("{}/standard", base_url), &[("fen", &fen_string)]) {
+            Ok(url) => url,
+            Err(error) => {
+                warn!("The tablebase url [{}] is invalid: {}", options.tablebase_url(), error);
+                return Vec::new();
+            }
+        };
+        // the 50-move rule is applied to the answer with the halfmove clock of the board,
+        // so positions that only differ in their move counters share the cached answer
+        let cache_key = cache_key(&url, 4);
+        let cached_response = ResponseCache::shared(&options).get(&cache_key);
+        let is_cached = cached_response.is_some();
+        let response = match cached_response {
+            Some(response) => response,
+            None => match self.http_client.get_text(url.as_str(), options.search_control()) {
+                Ok(response) => response,
+                Err(error) => {
+                    warn!("Request failed: {}", error);
+                    return Vec::new();
+                }
             },
+        };
+
+        let endgame_data: EndgameResponse = match serde_json::from_str(&response) {
+            Ok(data) => data,
             Err(error) => {
-                warn!("Request failed: {}", error);
+                warn!("Could not parse response string: {}", error);
+                return Vec::new();
+            }
+        };
+        if !is_cached {
+            ResponseCache::shared(&options).insert(&cache_key, response);
+        }
 
-                Vec::new()
-            },
+        info!("Received: [{} moves] for category [{:?}]", endgame_data.moves.len(), endgame_data.category);
+        if endgame_data.checkmate || endgame_data.stalemate || endgame_data.insufficient_material {
+            info!("The game is already over");
+            return Vec::new();
         }
+
+        let moves = order_moves(endgame_data.moves, board.halfmove_clock());
+        debug!("Ordered endgame moves: {:?}", moves);
+
+        moves.into_iter()
+            .map(|(category, mov)| {
+                let chess_move = Converter::sanitize_move(board, &Converter::convert_string_to_move(&mov.uci));
+                let (score, confidence) = category.expected_score();
+                ScoredMove::new(chess_move, score, confidence, "Endgame")
+            })
+            .collect()
     }
-}
\ No newline at end of file
+}
+
+/// Returns the moves with their results for the player to move, the best move first.
+fn order_moves(moves: Vec<EndgameMove>, halfmove_clock: u16) -> Vec<(EndgameCategory, EndgameMove)> {
+    let mut moves: Vec<(EndgameCategory, EndgameMove)> = moves.into_iter()
+        .map(|mov| (mov.category_for_player(halfmove_clock), mov))
+        .collect();
+    moves.sort_by_cached_key(|(category, mov)| Reverse((category.rank(), mov.order_within_category(*category))));
+    moves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a move whose category and distances are given from the view of the opponent.
+    fn endgame_move(uci: &str, category: EndgameCategory, dtz: Option<i32>, dtm: Option<i32>) -> EndgameMove {
+        EndgameMove {
+            uci: uci.to_string(),
+            zeroing: false,
+            checkmate: false,
+            stalemate: false,
+            insufficient_material: false,
+            dtz,
+            dtm,
+            category,
+        }
+    }
+
+    fn ordered_ucis(moves: Vec<EndgameMove>, halfmove_clock: u16) -> Vec<String> {
+        order_moves(moves, halfmove_clock).into_iter().map(|(_, mov)| mov.uci).collect()
+    }
+
+    #[test]
+    fn categories_are_ordered_from_win_to_loss() {
+        let moves = vec![
+            endgame_move("a1a2", EndgameCategory::Win, Some(5), Some(9)),
+            endgame_move("b1b2", EndgameCategory::Unknown, None, None),
+            endgame_move("c1c2", EndgameCategory::Draw, Some(0), None),
+            endgame_move("d1d2", EndgameCategory::CursedWin, Some(-120), None),
+            endgame_move("e1e2", EndgameCategory::Loss, Some(-3), Some(-7)),
+            endgame_move("f1f2", EndgameCategory::BlessedLoss, Some(110), None),
+            endgame_move("g1g2", EndgameCategory::MaybeLoss, Some(-40), None),
+        ];
+        assert_eq!(ordered_ucis(moves, 0), vec!["e1e2", "g1g2", "f1f2", "c1c2", "b1b2", "d1d2", "a1a2"]);
+    }
+
+    #[test]
+    fn wins_are_as_short_and_losses_as_long_as_possible() {
+        let mut mate = endgame_move("a7a8q", EndgameCategory::Loss, Some(-1), Some(-1));
+        mate.checkmate = true;
+        let mut zeroing = endgame_move("b2b4", EndgameCategory::Loss, Some(-30), Some(-31));
+        zeroing.zeroing = true;
+        let moves = vec![
+            endgame_move("c1c2", EndgameCategory::Loss, Some(-20), Some(-21)),
+            endgame_move("d1d2", EndgameCategory::Loss, Some(-10), Some(-11)),
+            zeroing,
+            mate,
+        ];
+        // the distance to mate decides, even if a move resets the 50-move rule
+        assert_eq!(ordered_ucis(moves, 0), vec!["a7a8q", "d1d2", "c1c2", "b2b4"]);
+
+        let moves = vec![
+            endgame_move("c1c2", EndgameCategory::Win, Some(10), Some(11)),
+            endgame_move("d1d2", EndgameCategory::Win, Some(30), Some(31)),
+        ];
+        assert_eq!(ordered_ucis(moves, 0), vec!["d1d2", "c1c2"]);
+    }
+
+    #[test]
+    fn zeroing_moves_are_preferred_without_distance_to_mate() {
+        let mut zeroing = endgame_move("b2b4", EndgameCategory::Loss, Some(-30), None);
+        zeroing.zeroing = true;
+        let moves = vec![endgame_move("c1c2", EndgameCategory::Loss, Some(-10), None), zeroing];
+        assert_eq!(ordered_ucis(moves, 0), vec!["b2b4", "c1c2"]);
+    }
+
+    #[test]
+    fn draws_that_end_the_game_come_first() {
+        let mut stalemate = endgame_move("h7h8", EndgameCategory::Draw, Some(0), None);
+        stalemate.stalemate = true;
+        let moves = vec![endgame_move("c1c2", EndgameCategory::Draw, Some(0), None), stalemate];
+        assert_eq!(ordered_ucis(moves, 0), vec!["h7h8", "c1c2"]);
+    }
+
+    #[test]
+    fn fifty_move_rule_spoils_long_wins_and_losses() {
+        let win = endgame_move("a1a2", EndgameCategory::Loss, Some(-60), Some(-61));
+        assert_eq!(win.category_for_player(0), EndgameCategory::Win);
+        assert_eq!(win.category_for_player(40), EndgameCategory::CursedWin);
+
+        let mut zeroing_win = endgame_move("b2b4", EndgameCategory::MaybeLoss, Some(-60), None);
+        zeroing_win.zeroing = true;
+        assert_eq!(zeroing_win.category_for_player(90), EndgameCategory::MaybeWin);
+
+        let loss = endgame_move("c1c2", EndgameCategory::Win, Some(60), Some(61));
+        assert_eq!(loss.category_for_player(40), EndgameCategory::BlessedLoss);
+
+        // a slow win that the rule spoils is worse than a short win, but better than a draw
+        let moves = vec![
+            endgame_move("d1d2", EndgameCategory::Draw, Some(0), None),
+            endgame_move("e1e2", EndgameCategory::Loss, Some(-80), Some(-81)),
+            endgame_move("f1f2", EndgameCategory::Loss, Some(-20), Some(-21)),
+        ];
+        assert_eq!(ordered_ucis(moves, 40), vec!["f1f2", "e1e2", "d1d2"]);
+    }
+
+    #[test]
+    fn categories_turn_around_for_the_opponent() {
+        assert_eq!(EndgameCategory::SyzygyLoss.for_opponent(), EndgameCategory::Win);
+        assert_eq!(EndgameCategory::BlessedLoss.for_opponent(), EndgameCategory::CursedWin);
+        assert_eq!(EndgameCategory::MaybeWin.for_opponent(), EndgameCategory::MaybeLoss);
+        assert_eq!(EndgameCategory::Unknown.for_opponent(), EndgameCategory::Unknown);
+    }
+}
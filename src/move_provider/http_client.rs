@@ -0,0 +1,382 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+use log::{debug, info, warn};
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Client, StatusCode};
+use tokio::runtime::{Builder, Handle};
+use tokio::select;
+use tokio::time::sleep;
+use crate::move_provider::search_control::SearchControl;
+
+/// Time a single request may take at most
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+/// Number of times a failed request is repeated
+const MAX_RETRIES: u32 = 2;
+/// Wait before the first repetition, it doubles with every further repetition
+const RETRY_BACKOFF: Duration = Duration::from_millis(200);
+/// Number of failed requests in a row after which the server is not asked anymore for a while
+const FAILURE_THRESHOLD: u32 = 3;
+/// Time the server is not asked after too many failed requests
+const CIRCUIT_COOLDOWN: Duration = Duration::from_secs(60);
+/// Time the server is not asked after it limited the rate, if it does not send a time itself.
+/// Lichess asks to wait a full minute.
+const RATE_LIMIT_PAUSE: Duration = Duration::from_secs(60);
+
+/// The reasons why a request did not return a result.
+#[derive(Debug)]
+pub enum HttpError {
+    /// The server is not asked, as it failed too often or limited the rate
+    CircuitOpen(Duration),
+    /// The server limited the rate of the requests
+    RateLimited(Duration),
+    /// The server answered with an error status
+    Status(StatusCode),
+    /// The request did not finish in time or could not be sent
+    Request(reqwest::Error),
+    /// The gui sent stop while waiting for the server
+    Stopped,
+}
+
+impl HttpError {
+    /// Returns whether the same request may succeed when it is repeated.
+    fn is_transient(&self) -> bool {
+        match self {
+            HttpError::Status(status) => status.is_server_error(),
+            HttpError::Request(_) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpError::CircuitOpen(remaining) => write!(f, "the server is not asked for another {:?}", remaining),
+            HttpError::RateLimited(pause) => write!(f, "the server limited the rate, pausing for {:?}", pause),
+            HttpError::Status(status) => write!(f, "the server answered with status {}", status),
+            HttpError::Request(error) => write!(f, "the request failed: {}", error),
+            HttpError::Stopped => write!(f, "the request was stopped"),
+        }
+    }
+}
+
+/// Stops asking a server that failed too often in a row, so a broken server does not
+/// delay every move. After the cooldown the server is asked again, and one more failure
+/// opens the circuit again.
+#[derive(Debug, Default)]
+struct CircuitBreaker {
+    consecutive_failures: u32,
+    /// The server is not asked before this point in time
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    /// Returns the time the server is still not asked, None if it may be asked.
+    fn remaining_pause(&self) -> Option<Duration> {
+        self.open_until
+            .map(|open_until| open_until.saturating_duration_since(Instant::now()))
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    fn record_success(&mut self) {
+        self.consecutive_failures = 0;
+        self.open_until = None;
+    }
+
+    /// Returns whether the circuit was opened by the failure.
+    fn record_failure(&mut self) -> bool {
+        self.consecutive_failures += 1;
+        if self.consecutive_failures >= FAILURE_THRESHOLD {
+            self.open_until = Some(Instant::now() + CIRCUIT_COOLDOWN);
+            // a single failure after the cooldown opens the circuit again
+            self.consecutive_failures = FAILURE_THRESHOLD - 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn pause(&mut self, pause: Duration) {
+        self.open_until = Some(Instant::now() + pause);
+    }
+}
+
+/// Sends the requests of a move provider to a web service.
+///
+/// Every request is limited in time, also by the time left for the move, and is aborted when
+/// the gui sends stop. Failed requests are repeated with growing waits in between.
+/// A server that failed too often or limited the rate is not asked for a while.
+#[derive(Debug)]
+pub struct HttpClient {
+    /// The name of the move provider, for the log
+    name: &'static str,
+    client: Client,
+    circuit_breaker: CircuitBreaker,
+}
+
+impl HttpClient {
+    pub fn new(name: &'static str) -> HttpClient {
+        HttpClient {
+            name,
+            client: Client::new(),
+            circuit_breaker: CircuitBreaker::default(),
+        }
+    }
+
+    /// Requests the text at the given url and waits for it.
+    /// Uses the runtime of the engine, so it has to be called outside of async code.
+    pub fn get_text(&mut self, url: &str, search_control: &SearchControl) -> Result<String, HttpError> {
+        match Handle::try_current() {
+            Ok(handle) => handle.block_on(self.get_text_async(url, search_control)),
+            Err(_) => {
+                let runtime = Builder::new_current_thread().enable_all().build()
+                    .expect("A runtime can be built for the request");
+                runtime.block_on(self.get_text_async(url, search_control))
+            }
+        }
+    }
+
+    /// Requests the text at the given url, repeating the request if it failed.
+    pub async fn get_text_async(&mut self, url: &str, search_control: &SearchControl) -> Result<String, HttpError> {
+        if let Some(remaining) = self.circuit_breaker.remaining_pause() {
+            debug!("[{}] skips the request, the server is not asked for another [{:?}]", self.name, remaining);
+            return Err(HttpError::CircuitOpen(remaining));
+        }
+
+        let mut attempt = 0;
+        loop {
+            let timeout = search_control.time_left().map_or(REQUEST_TIMEOUT, |time_left| time_left.min(REQUEST_TIMEOUT));
+            if timeout.is_zero() {
+                return Err(HttpError::Stopped);
+            }
+
+            let result = select! {
+                result = self.request(url, timeout) => result,
+                _ = search_control.stopped() => Err(HttpError::Stopped),
+            };
+
+            match result {
+                Ok(value) => {
+                    self.circuit_breaker.record_success();
+                    return Ok(value);
+                }
+                Err(HttpError::RateLimited(pause)) => {
+                    warn!("[{}] was rate limited, pausing for [{:?}]", self.name, pause);
+                    self.circuit_breaker.pause(pause);
+                    return Err(HttpError::RateLimited(pause));
+                }
+                Err(error) if error.is_transient() && attempt < MAX_RETRIES => {
+                    let backoff = RETRY_BACKOFF * 2u32.pow(attempt);
+                    attempt += 1;
+                    warn!("[{}] request failed, repeating it in [{:?}]: {}", self.name, backoff, error);
+                    select! {
+                        _ = sleep(backoff) => {},
+                        _ = search_control.stopped() => return Err(HttpError::Stopped),
+                    }
+                }
+                Err(error) => {
+                    if error.is_transient() && self.circuit_breaker.record_failure() {
+                        info!("[{}] failed too often, the server is not asked for [{:?}]", self.name, CIRCUIT_COOLDOWN);
+                    }
+                    return Err(error);
+                }
+            }
+        }
+    }
+
+    async fn request(&self, url: &str, timeout: Duration) -> Result<String, HttpError> {
+        debug!("[{}] requests [{}] with timeout [{:?}]", self.name, url, timeout);
+        let response = self.client.get(url).timeout(timeout).send().await.map_err(HttpError::Request)?;
+
+        let status = response.status();
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let pause = response.headers().get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .map_or(RATE_LIMIT_PAUSE, Duration::from_secs);
+            return Err(HttpError::RateLimited(pause));
+        }
+        if !status.is_success() {
+            return Err(HttpError::Status(status));
+        }
+
+        let text = response.text().await.map_err(HttpError::Request)?;
+        debug!("[{}] received answer [{}]", self.name, text);
+        Ok(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_util::sync::CancellationToken;
+    use crate::datamodel::enums::color::Color;
+    use crate::datamodel::search_limits::SearchLimits;
+    use crate::move_provider::mock_server::{MockResponse, MockServer};
+
+    #[tokio::test]
+    async fn answer_is_returned() {
+        let server = MockServer::start(vec![MockResponse::ok("{\"moves\":[]}")]);
+        let mut client = HttpClient::new("Test");
+
+        let result = client.get_text_async(&server.url(), &SearchControl::default()).await;
+        assert_eq!(result.unwrap(), "{\"moves\":[]}");
+        assert_eq!(server.request_times().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn slow_requests_time_out() {
+        let slow = MockResponse { delay: Duration::from_secs(30), ..MockResponse::ok("late") };
+        let server = MockServer::start(vec![slow]);
+        let mut client = HttpClient::new("Test");
+
+        let start = Instant::now();
+        let result = client.get_text_async(&server.url(), &SearchControl::default()).await;
+        let elapsed = start.elapsed();
+
+        assert!(matches!(result, Err(HttpError::Request(ref error)) if error.is_timeout()), "{:?}", result);
+        // every attempt waits for the timeout, with the backoffs in between
+        assert_eq!(server.request_times().len(), 1 + MAX_RETRIES as usize);
+        assert!(elapsed >= REQUEST_TIMEOUT * 3 + RETRY_BACKOFF * 3, "{:?}", elapsed);
+        assert!(elapsed < REQUEST_TIMEOUT * 3 + RETRY_BACKOFF * 3 + Duration::from_secs(1), "{:?}", elapsed);
+    }
+
+    #[tokio::test]
+    async fn requests_end_with_the_time_of_the_move() {
+        let slow = MockResponse { delay: Duration::from_secs(30), ..MockResponse::ok("late") };
+        let server = MockServer::start(vec![slow]);
+        let mut client = HttpClient::new("Test");
+        let mut limits = SearchLimits::new();
+        limits.set_move_time(Some(350));
+        let search_control = SearchControl::new(limits, CancellationToken::new());
+        search_control.start(Color::WHITE);
+
+        let start = Instant::now();
+        let result = client.get_text_async(&server.url(), &search_control).await;
+
+        assert!(result.is_err());
+        assert!(start.elapsed() < REQUEST_TIMEOUT, "{:?}", start.elapsed());
+    }
+
+    #[tokio::test]
+    async fn stop_ends_the_request() {
+        let slow = MockResponse { delay: Duration::from_secs(30), ..MockResponse::ok("late") };
+        let server = MockServer::start(vec![slow]);
+        let mut client = HttpClient::new("Test");
+        let search_control = SearchControl::default();
+        let stopper = search_control.clone();
+        tokio::spawn(async move {
+            sleep(Duration::from_millis(100)).await;
+            stopper.stop();
+        });
+
+        let start = Instant::now();
+        let result = client.get_text_async(&server.url(), &search_control).await;
+
+        assert!(matches!(result, Err(HttpError::Stopped)), "{:?}", result);
+        assert!(start.elapsed() < Duration::from_secs(1), "{:?}", start.elapsed());
+    }
+
+    #[tokio::test]
+    async fn server_errors_are_repeated_with_doubling_backoff() {
+        let server = MockServer::start(vec![MockResponse::new(500), MockResponse::new(503), MockResponse::ok("third")]);
+        let mut client = HttpClient::new("Test");
+
+        let result = client.get_text_async(&server.url(), &SearchControl::default()).await;
+        assert_eq!(result.unwrap(), "third");
+
+        let times = server.request_times();
+        assert_eq!(times.len(), 3);
+        let first_wait = times[1] - times[0];
+        let second_wait = times[2] - times[1];
+        assert!(first_wait >= RETRY_BACKOFF && first_wait < RETRY_BACKOFF * 2, "{:?}", first_wait);
+        assert!(second_wait >= RETRY_BACKOFF * 2 && second_wait < RETRY_BACKOFF * 4, "{:?}", second_wait);
+        assert_eq!(client.circuit_breaker.consecutive_failures, 0);
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_repeated() {
+        let server = MockServer::start(vec![MockResponse::new(404), MockResponse::ok("second")]);
+        let mut client = HttpClient::new("Test");
+
+        let result = client.get_text_async(&server.url(), &SearchControl::default()).await;
+        assert!(matches!(result, Err(HttpError::Status(StatusCode::NOT_FOUND))), "{:?}", result);
+        assert_eq!(server.request_times().len(), 1);
+        // the server works, the request was wrong
+        assert_eq!(client.circuit_breaker.consecutive_failures, 0);
+        assert!(client.circuit_breaker.remaining_pause().is_none());
+    }
+
+    #[tokio::test]
+    async fn rate_limit_pauses_for_the_time_of_the_server() {
+        let limited = MockResponse { retry_after: Some("7"), ..MockResponse::new(429) };
+        let server = MockServer::start(vec![limited, MockResponse::ok("later")]);
+        let mut client = HttpClient::new("Test");
+
+        let result = client.get_text_async(&server.url(), &SearchControl::default()).await;
+        assert!(matches!(result, Err(HttpError::RateLimited(pause)) if pause == Duration::from_secs(7)), "{:?}", result);
+
+        // the server is not asked again during the pause
+        let result = client.get_text_async(&server.url(), &SearchControl::default()).await;
+        assert!(matches!(result, Err(HttpError::CircuitOpen(remaining))
+            if remaining <= Duration::from_secs(7) && remaining > Duration::from_secs(6)), "{:?}", result);
+        assert_eq!(server.request_times().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn rate_limit_without_retry_after_pauses_for_the_default_time() {
+        let server = MockServer::start(vec![MockResponse::new(429)]);
+        let mut client = HttpClient::new("Test");
+
+        let result = client.get_text_async(&server.url(), &SearchControl::default()).await;
+        assert!(matches!(result, Err(HttpError::RateLimited(pause)) if pause == RATE_LIMIT_PAUSE), "{:?}", result);
+
+        let result = client.get_text_async(&server.url(), &SearchControl::default()).await;
+        assert!(matches!(result, Err(HttpError::CircuitOpen(remaining)) if remaining > RATE_LIMIT_PAUSE - Duration::from_secs(1)), "{:?}", result);
+        assert_eq!(server.request_times().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn circuit_opens_after_failures_and_closes_after_the_cooldown() {
+        let requests_per_call = 1 + MAX_RETRIES as usize;
+        let mut responses = vec![MockResponse::new(500); requests_per_call * FAILURE_THRESHOLD as usize];
+        responses.push(MockResponse::ok("recovered"));
+        let server = MockServer::start(responses);
+        let mut client = HttpClient::new("Test");
+
+        for _ in 0..FAILURE_THRESHOLD {
+            let result = client.get_text_async(&server.url(), &SearchControl::default()).await;
+            assert!(matches!(result, Err(HttpError::Status(StatusCode::INTERNAL_SERVER_ERROR))), "{:?}", result);
+        }
+        let result = client.get_text_async(&server.url(), &SearchControl::default()).await;
+        assert!(matches!(result, Err(HttpError::CircuitOpen(remaining))
+            if remaining > CIRCUIT_COOLDOWN - Duration::from_secs(1)), "{:?}", result);
+        assert_eq!(server.request_times().len(), requests_per_call * FAILURE_THRESHOLD as usize);
+
+        // let the cooldown pass
+        client.circuit_breaker.open_until = Some(Instant::now());
+        let result = client.get_text_async(&server.url(), &SearchControl::default()).await;
+        assert_eq!(result.unwrap(), "recovered");
+        assert_eq!(client.circuit_breaker.consecutive_failures, 0);
+        assert!(client.circuit_breaker.remaining_pause().is_none());
+    }
+
+    #[test]
+    fn one_failure_after_the_cooldown_opens_the_circuit_again() {
+        let mut circuit_breaker = CircuitBreaker::default();
+        for _ in 1..FAILURE_THRESHOLD {
+            assert!(!circuit_breaker.record_failure());
+        }
+        assert!(circuit_breaker.record_failure());
+        assert!(circuit_breaker.remaining_pause().is_some());
+
+        circuit_breaker.open_until = Some(Instant::now());
+        assert!(circuit_breaker.remaining_pause().is_none());
+        assert!(circuit_breaker.record_failure());
+        assert!(circuit_breaker.remaining_pause().is_some());
+
+        circuit_breaker.record_success();
+        assert!(circuit_breaker.remaining_pause().is_none());
+        assert!(!circuit_breaker.record_failure());
+    }
+}
@@ -2,23 +2,39 @@ use log::{debug, info};
 use crate::datamodel::board::Board;
 use crate::datamodel::chess_move::ChessMove;
 use crate::datamodel::options::Options;
+use crate::evaluation::Evaluator;
 use crate::move_provider::MoveProvider;
+use crate::move_provider::scored_move::ScoredMove;
 use crate::rules::piece_rule::PieceRule;
 use crate::rules::RulesProvider;
 
+/// Confidence of the scores, which rest on the material of a search without quiescence
+const MINMAX_CONFIDENCE: f64 = 0.2;
+
 #[derive(Debug)]
-pub struct MinMax;
+pub struct MinMax<E: Evaluator> {
+    evaluator: E,
+}
+
+impl<E: Evaluator> MinMax<E> {
+    pub fn new(evaluator: E) -> MinMax<E> {
+        MinMax {
+            evaluator,
+        }
+    }
+}
 
 use rayon::prelude::*;
 use crate::converter::converter::Converter;
 use crate::move_provider::minmax::recursive_minmax_task::{recursive_minmax_task, MinmaxTaskContext};
 
-impl MoveProvider for MinMax {
-    fn get_recommended_moves(&mut self, board: &Board, options: Options) -> Vec<ChessMove> {
+impl<E: Evaluator> MoveProvider for MinMax<E> {
+    fn get_recommended_moves(&mut self, board: &Board, options: Options) -> Vec<ScoredMove> {
         info!("Starting calculation of minmax for position {:?}.", Converter::convert_board_to_string(board));
         let moves = PieceRule::get_legal_moves(board, &board.next_color());
 
         info!("Got legal moves: {:?}", &moves);
+        self.evaluator.configure(&options);
 
         let mut evaluated_moves: Vec<(ChessMove, i32)> =
             moves.par_iter().map( |mov|
@@ -29,6 +45,7 @@ impl MoveProvider for MinMax {
                         current_level: 1,
                         player_color: board.next_color(),
                         max_level: options.recursion_depth() as i32,
+                        evaluator: &self.evaluator,
                     }
                 ))
             ).collect();
@@ -36,7 +53,10 @@ impl MoveProvider for MinMax {
         debug!("Calculated minmax moves");
 
         evaluated_moves.sort_by_key(|(_, rating)| *rating);
-        evaluated_moves.into_iter().map(|(mov, _)| mov).collect()
+        // lower ratings are better, and they only rate the change of the evaluation by a shallow search
+        evaluated_moves.into_iter()
+            .map(|(mov, rating)| ScoredMove::new(mov, ScoredMove::score_of_centipawns(-rating), MINMAX_CONFIDENCE, "MinMax"))
+            .collect()
 
     }
 }
\ No newline at end of file
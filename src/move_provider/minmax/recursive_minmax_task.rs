@@ -1,29 +1,31 @@
 use crate::datamodel::board::Board;
 use crate::datamodel::chess_move::ChessMove;
 use crate::datamodel::enums::color::Color;
-
-
-use crate::datamodel::field::Field;
+use crate::evaluation::Evaluator;
 use crate::rules::piece_rule::PieceRule;
 use crate::rules::RulesProvider;
 
-use rayon::prelude::*;
+/// Rating of a checkmate in centipawns
+const CHECKMATE_RATING: i32 = 10_000;
+/// Ratings beyond this bound contain a checkmate
+const CHECKMATE_BOUND: i32 = 6_000;
 
-pub struct MinmaxTaskContext {
+pub struct MinmaxTaskContext<'a, E: Evaluator> {
     pub board: Board,
     pub chess_move: ChessMove,
     pub current_level: i32,
     pub player_color: Color,
     pub max_level: i32,
+    pub evaluator: &'a E,
 }
 
-pub fn recursive_minmax_task(context: MinmaxTaskContext) -> i32 {
+pub fn recursive_minmax_task<E: Evaluator>(context: MinmaxTaskContext<E>) -> i32 {
     let mut new_board = context.board.clone();
-
-    let rating: i32 = rate_move(&new_board, &context.chess_move, context.player_color);
     new_board.play_move(&context.chess_move);
 
-    if context.current_level >= context.max_level || rating.abs() > 60 {
+    let rating: i32 = rate_move(context.evaluator, &context.board, &new_board, context.player_color);
+
+    if context.current_level >= context.max_level || rating.abs() > CHECKMATE_BOUND {
         return rating;
     }
 
@@ -32,45 +34,38 @@ pub fn recursive_minmax_task(context: MinmaxTaskContext) -> i32 {
 
 
     // for each legal move compute the rating
-    legal_moves.par_iter().map( |legal_move|
+    legal_moves.iter().map( |legal_move|
          recursive_minmax_task(MinmaxTaskContext{
             board: new_board.clone(),
             chess_move: *legal_move,
             current_level: context.current_level + 1,
             ..context
         })
-    ).max().map_or(-100, |rating_increment| rating_increment + rating)
+    ).max().map_or(-CHECKMATE_RATING, |rating_increment| rating_increment + rating)
 }
 
-/// rates the given move for the player of the given color
-fn rate_move(board: &Board, chess_move: &ChessMove, player_color: Color) -> i32 {
-    let mut evaluation: i32 = 0;
-    let field: Field = *chess_move.to_field();
-
+/// rates the given move for the player of the given color by the change of the evaluation.
+/// lower ratings are better for the player.
+fn rate_move<E: Evaluator>(evaluator: &E, board: &Board, new_board: &Board, player_color: Color) -> i32 {
     let opponent_color: Color = !player_color;
 
-    let piece_to_hit = board.get_piece(&field);
-
-    if let Some(piece_to_hit) = piece_to_hit {
-        if board.next_color() == player_color {
-            evaluation -= piece_to_hit.piece_type().value() as i32;
-        } else {
-            evaluation += piece_to_hit.piece_type().value() as i32;
-        }
-    }
+    let mut evaluation: i32 = evaluate_for(evaluator, board, player_color) - evaluate_for(evaluator, new_board, player_color);
 
-    if PieceRule::is_checked(board, &player_color) {
-        evaluation += 1;
-    }
-    if PieceRule::is_checked(board, &opponent_color) {
-        evaluation -= 1;
-    }
     if PieceRule::is_checkmated(board, &player_color) {
-        evaluation += 100;
+        evaluation += CHECKMATE_RATING;
     }
     if PieceRule::is_checkmated(board, &opponent_color) {
-        evaluation -= 100;
+        evaluation -= CHECKMATE_RATING;
     }
 
     evaluation
-}
\ No newline at end of file
+}
+
+/// rates the position from the view of the player of the given color
+fn evaluate_for<E: Evaluator>(evaluator: &E, board: &Board, color: Color) -> i32 {
+    if board.next_color() == color {
+        evaluator.evaluate(board)
+    } else {
+        -evaluator.evaluate(board)
+    }
+}
@@ -0,0 +1,96 @@
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::runtime::Builder;
+use tokio::time::sleep;
+
+/// An answer of the mock server, sent after the delay.
+#[derive(Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub retry_after: Option<&'static str>,
+    pub body: &'static str,
+    pub delay: Duration,
+}
+
+impl MockResponse {
+    pub fn new(status: u16) -> MockResponse {
+        MockResponse { status, retry_after: None, body: "", delay: Duration::ZERO }
+    }
+
+    pub fn ok(body: &'static str) -> MockResponse {
+        MockResponse { body, ..MockResponse::new(200) }
+    }
+}
+
+/// A local http server for the tests of the web move providers. It answers the requests with
+/// the given responses in turn, repeating the last one, and records when the requests arrived.
+///
+/// The server runs on a thread of its own, so it serves async tests as well as move providers
+/// that block on their requests.
+pub struct MockServer {
+    base_url: String,
+    request_times: Arc<Mutex<Vec<Instant>>>,
+}
+
+impl MockServer {
+    pub fn start(responses: Vec<MockResponse>) -> MockServer {
+        let request_times = Arc::new(Mutex::new(Vec::new()));
+        let recorded_times = request_times.clone();
+        let (address_sender, address_receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+            runtime.block_on(async move {
+                let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+                address_sender.send(listener.local_addr().unwrap()).unwrap();
+                loop {
+                    let Ok((mut stream, _)) = listener.accept().await else { return };
+                    let response = {
+                        let mut times = recorded_times.lock().unwrap();
+                        times.push(Instant::now());
+                        responses[(times.len() - 1).min(responses.len() - 1)].clone()
+                    };
+                    tokio::spawn(async move {
+                        // the requests are gets without a body, so the header ends the request
+                        let mut request = Vec::new();
+                        let mut buffer = [0u8; 1024];
+                        while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                            match stream.read(&mut buffer).await {
+                                Ok(0) | Err(_) => return,
+                                Ok(read) => request.extend_from_slice(&buffer[..read]),
+                            }
+                        }
+                        sleep(response.delay).await;
+                        let retry_after = response.retry_after
+                            .map_or(String::new(), |retry_after| format!("Retry-After: {}\r\n", retry_after));
+                        let answer = format!(
+                            "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n{}\r\n{}",
+                            response.status, response.body.len(), retry_after, response.body);
+                        let _ = stream.write_all(answer.as_bytes()).await;
+                    });
+                }
+            });
+        });
+
+        let address = address_receiver.recv().unwrap();
+        MockServer { base_url: format!("http://{}", address), request_times }
+    }
+
+    /// Returns the url of the server without a trailing slash, as the url options expect it.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Returns an url of a request, the server answers every path the same.
+    pub fn url(&self) -> String {
+        format!("{}/standard?fen=test", self.base_url)
+    }
+
+    pub fn request_times(&self) -> Vec<Instant> {
+        self.request_times.lock().unwrap().clone()
+    }
+}
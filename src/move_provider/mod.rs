@@ -2,16 +2,42 @@ use std::fmt::Debug;
 use crate::datamodel::board::Board;
 use crate::datamodel::chess_move::ChessMove;
 use crate::datamodel::options::Options;
+use crate::move_provider::scored_move::ScoredMove;
 
+pub mod http_client;
+#[cfg(test)]
+mod mock_server;
+pub mod response_cache;
 pub mod openings;
+pub mod polyglot_book;
 pub mod endgame;
+pub mod syzygy_tablebases;
+pub mod syzygy_endgame;
 
 pub mod minmax;
 
 pub mod negamax;
 
+pub mod search_control;
+
+pub mod scored_move;
+
 /// Provides functionality to obtain recommended moves for a given chess situation.
 pub trait MoveProvider: Debug {
-    /// Provides a vector of recommended ChessMoves for the given position on the Board.
-    fn get_recommended_moves(&mut self, board: &Board, options: Options) -> Vec<ChessMove>;
+    /// Provides the recommended moves for the given position on the Board with their scores, best move first.
+    /// An empty vector means that the move provider has no recommendation for the position.
+    fn get_recommended_moves(&mut self, board: &Board, options: Options) -> Vec<ScoredMove>;
+
+    /// Provides the expected answer of the opponent to the given move, which the engine ponders on.
+    /// Only meaningful for a move returned by the last call of get_recommended_moves.
+    fn get_ponder_move(&self, _board: &Board, _chess_move: &ChessMove) -> Option<ChessMove> {
+        None
+    }
+
+    /// The gui starts a new game, so everything learned about the previous game is forgotten.
+    fn new_game(&mut self) {}
+
+    /// The gui set up the given position, which the next search starts from.
+    /// It may continue the previous position or belong to another game.
+    fn position_changed(&mut self, _board: &Board) {}
 }
\ No newline at end of file
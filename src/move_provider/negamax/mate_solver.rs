@@ -0,0 +1,326 @@
+use std::cmp::Reverse;
+use log::{debug, info};
+use crate::converter::converter::Converter;
+use crate::datamodel::board::Board;
+use crate::datamodel::chess_move::ChessMove;
+use crate::datamodel::options::Options;
+use crate::evaluation::evaluation_weights::EvaluationWeights;
+use crate::evaluation::piece_square_evaluator::PieceSquareWeights;
+use crate::move_provider::MoveProvider;
+use crate::move_provider::scored_move::ScoredMove;
+use crate::move_provider::negamax::recursive_negamax_task::Evaluation;
+use crate::move_provider::search_control::SearchControl;
+use crate::rules::piece_rule::PieceRule;
+use crate::rules::RulesProvider;
+
+/// Number of nodes that are searched between two checks whether the search has to stop
+const NODES_BETWEEN_STOP_CHECKS: u64 = 1024;
+
+/// A forced mate found by the mate search.
+#[derive(Clone, Debug)]
+pub struct MateSolution {
+    /// Always PlayerHasCheckmateIn with the number of moves of the mating player
+    pub evaluation: Evaluation,
+    /// The moves of both players up to the checkmate against the most stubborn defense
+    pub line: Vec<ChessMove>,
+}
+
+/// An implementation of MoveProvider that proves forced mates.
+/// It is only asked for moves, if the gui requested a mate search with go mate.
+#[derive(Debug)]
+pub struct MateSolver {
+    /// The mating line of the last search
+    line: Vec<ChessMove>,
+    /// The weights whose piece values order the captures
+    weights: PieceSquareWeights,
+    /// The evaluation parameters file the weights were loaded from
+    eval_params: String,
+}
+
+impl MateSolver {
+    pub fn new() -> MateSolver {
+        MateSolver {
+            line: Vec::new(),
+            weights: PieceSquareWeights::default(),
+            eval_params: String::new(),
+        }
+    }
+
+    fn configure(&mut self, options: &Options) {
+        if options.eval_params() == self.eval_params {
+            return;
+        }
+        self.eval_params = options.eval_params().to_string();
+        self.weights = EvaluationWeights::from_eval_params(&self.eval_params).piece_square;
+    }
+}
+
+impl MoveProvider for MateSolver {
+    fn get_recommended_moves(&mut self, board: &Board, options: Options) -> Vec<ScoredMove> {
+        self.line.clear();
+        let Some(max_moves) = options.search_control().limits().mate() else {
+            return Vec::new();
+        };
+
+        info!("Searching mate in [{}] moves for position {:?}.", max_moves, Converter::convert_board_to_string(board));
+        self.configure(&options);
+        let mut mate_search = MateSearch::new(options.search_control(), options.mate_search_checks_only(), &self.weights);
+        let solution = mate_search.solve(board, max_moves);
+        debug!("Mate search searched [{}] nodes", mate_search.nodes);
+
+        match solution {
+            Some(solution) => {
+                let line: Vec<String> = solution.line.iter().map(Converter::convert_move_to_string).collect();
+                println!("info depth {} score {} pv {}", solution.line.len(), solution.evaluation.to_uci_score(), line.join(" "));
+                info!("Found mate [{:?}] with line [{:?}]", solution.evaluation, line);
+
+                self.line = solution.line;
+                // a proven mate wins for sure
+                vec![ScoredMove::new(self.line[0], 1.0, 1.0, "MateSolver").with_principal_variation(self.line[1..].to_vec())]
+            }
+            None => {
+                info!("No forced mate in [{}] moves found", max_moves);
+                Vec::new()
+            }
+        }
+    }
+
+    fn get_ponder_move(&self, _board: &Board, chess_move: &ChessMove) -> Option<ChessMove> {
+        if self.line.first() == Some(chess_move) {
+            self.line.get(1).copied()
+        } else {
+            None
+        }
+    }
+}
+
+/// Proves or refutes forced mates of the player to move.
+///
+/// If only checks are searched, the mating player may only give check. This finds most mates
+/// of puzzles much faster, but a position without such a mate may still have a quiet one.
+pub struct MateSearch<'a> {
+    search_control: &'a SearchControl,
+    checks_only: bool,
+    /// The piece values of the weights order the captures
+    weights: &'a PieceSquareWeights,
+    pub nodes: u64,
+    pub stopped: bool,
+}
+
+impl<'a> MateSearch<'a> {
+    pub fn new(search_control: &'a SearchControl, checks_only: bool, weights: &'a PieceSquareWeights) -> Self {
+        MateSearch {
+            search_control,
+            checks_only,
+            weights,
+            nodes: 0,
+            stopped: false,
+        }
+    }
+
+    /// Searches the shortest forced mate of the player to move in at most the given number of moves.
+    /// Returns None if there is no such mate or the search was stopped.
+    pub fn solve(&mut self, board: &Board, max_moves: u32) -> Option<MateSolution> {
+        for moves in 1..=max_moves {
+            if let Some(line) = self.find_mate(board, moves) {
+                return Some(MateSolution {
+                    evaluation: Evaluation::PlayerHasCheckmateIn(moves as i32),
+                    line,
+                });
+            }
+            if self.stopped {
+                info!("Mate search stopped while searching mate in [{}] moves", moves);
+                return None;
+            }
+            debug!("There is no mate in [{}] moves", moves);
+        }
+        None
+    }
+
+    /// Returns the mating line, if the player to move mates in at most the given number of moves.
+    fn find_mate(&mut self, board: &Board, moves: u32) -> Option<Vec<ChessMove>> {
+        if self.count_node() {
+            return None;
+        }
+
+        for chess_move in self.attacking_moves(board) {
+            let mut new_board = board.clone();
+            new_board.play_move(&chess_move);
+
+            if let Some(mut line) = self.refute_defenses(&new_board, moves) {
+                line.insert(0, chess_move);
+                return Some(line);
+            }
+            if self.stopped {
+                return None;
+            }
+        }
+        None
+    }
+
+    /// Returns the line of the most stubborn defense, if every defense of the player to move is mated.
+    /// The given number of moves includes the move of the mating player that led to the board.
+    fn refute_defenses(&mut self, board: &Board, moves: u32) -> Option<Vec<ChessMove>> {
+        let color = board.next_color();
+        let defenses = PieceRule::get_legal_moves(board, &color);
+        if defenses.is_empty() {
+            // stalemate is no mate
+            return if PieceRule::is_checked(board, &color) { Some(Vec::new()) } else { None };
+        }
+        if moves <= 1 {
+            return None;
+        }
+
+        let mut most_stubborn: Option<(u32, Vec<ChessMove>)> = None;
+        for defense in defenses {
+            let mut new_board = board.clone();
+            new_board.play_move(&defense);
+
+            // find the shortest mate against this defense
+            let mut refutation = None;
+            for remaining_moves in 1..moves {
+                if let Some(line) = self.find_mate(&new_board, remaining_moves) {
+                    refutation = Some((remaining_moves, line));
+                    break;
+                }
+                if self.stopped {
+                    return None;
+                }
+            }
+
+            // a single defense that is not mated refutes the mate
+            let (remaining_moves, mut line) = refutation?;
+            if most_stubborn.as_ref().is_none_or(|(longest, _)| remaining_moves > *longest) {
+                line.insert(0, defense);
+                most_stubborn = Some((remaining_moves, line));
+            }
+        }
+        most_stubborn.map(|(_, line)| line)
+    }
+
+    /// Returns the moves of the mating player, checks first, then captures of valuable pieces.
+    fn attacking_moves(&self, board: &Board) -> Vec<ChessMove> {
+        let opponent_color = !board.next_color();
+        let mut moves: Vec<(ChessMove, bool)> = PieceRule::get_legal_moves(board, &board.next_color())
+            .into_iter()
+            .map(|chess_move| {
+                let mut new_board = board.clone();
+                new_board.play_move(&chess_move);
+                (chess_move, PieceRule::is_checked(&new_board, &opponent_color))
+            })
+            .filter(|(_, gives_check)| *gives_check || !self.checks_only)
+            .collect();
+
+        moves.sort_by_cached_key(|(chess_move, gives_check)| {
+            let capture_value = board.get_piece(chess_move.to_field())
+                .map_or(0, |victim| self.weights.piece_value(victim.piece_type()));
+            Reverse((*gives_check, capture_value))
+        });
+        moves.into_iter().map(|(chess_move, _)| chess_move).collect()
+    }
+
+    /// Counts the node and returns whether the search has to stop.
+    fn count_node(&mut self) -> bool {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(NODES_BETWEEN_STOP_CHECKS) && self.search_control.should_stop() {
+            self.stopped = true;
+        }
+        self.stopped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_util::sync::CancellationToken;
+    use crate::datamodel::enums::difficulty::Difficulty;
+    use crate::datamodel::search_limits::SearchLimits;
+
+    fn solve(fen: &str, max_moves: u32, checks_only: bool) -> Option<MateSolution> {
+        let board = Converter::convert_string_to_board(fen.to_string());
+        let search_control = SearchControl::default();
+        let weights = PieceSquareWeights::default();
+        MateSearch::new(&search_control, checks_only, &weights).solve(&board, max_moves)
+    }
+
+    fn line(solution: &MateSolution) -> Vec<String> {
+        solution.line.iter().map(Converter::convert_move_to_string).collect()
+    }
+
+    /// Plays the line and returns whether it ends in checkmate
+    fn ends_in_checkmate(fen: &str, solution: &MateSolution) -> bool {
+        let mut board = Converter::convert_string_to_board(fen.to_string());
+        for chess_move in &solution.line {
+            assert!(PieceRule::get_legal_moves(&board, &board.next_color()).contains(chess_move));
+            board.play_move(chess_move);
+        }
+        PieceRule::is_checked(&board, &board.next_color()) && PieceRule::get_legal_moves(&board, &board.next_color()).is_empty()
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        let fen = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1";
+        let solution = solve(fen, 3, false).unwrap();
+        assert_eq!(solution.evaluation, Evaluation::PlayerHasCheckmateIn(1));
+        assert_eq!(line(&solution), vec!["a1a8"]);
+    }
+
+    #[test]
+    fn finds_mate_in_two() {
+        let fen = "7k/8/5K2/8/8/8/8/6R1 w - - 0 1";
+        let solution = solve(fen, 3, false).unwrap();
+        assert_eq!(solution.evaluation, Evaluation::PlayerHasCheckmateIn(2));
+        // the king takes the last flight squares with a quiet move
+        assert_eq!(line(&solution), vec!["f6f7", "h8h7", "g1h1"]);
+    }
+
+    #[test]
+    fn finds_mate_in_three_against_most_stubborn_defense() {
+        let fen = "8/8/7k/8/8/8/R7/1R4K1 w - - 0 1";
+        assert!(solve(fen, 2, false).is_none());
+
+        let solution = solve(fen, 3, false).unwrap();
+        assert_eq!(solution.evaluation, Evaluation::PlayerHasCheckmateIn(3));
+        assert_eq!(solution.line.len(), 5);
+        assert!(ends_in_checkmate(fen, &solution));
+    }
+
+    #[test]
+    fn finds_no_mate_if_there_is_none() {
+        // king and rook need more moves to mate a king in the center
+        let fen = "4k3/8/8/8/8/8/8/R3K3 w - - 0 1";
+        assert!(solve(fen, 3, false).is_none());
+        assert!(solve(fen, 3, true).is_none());
+    }
+
+    #[test]
+    fn checks_only_misses_quiet_mates() {
+        let fen = "7k/8/5K2/8/8/8/8/6R1 w - - 0 1";
+        assert!(solve(fen, 2, true).is_none());
+
+        // a mate of checks only is still found
+        let fen = "r5k1/5ppp/8/1Q6/8/8/5PPP/4R1K1 w - - 0 1";
+        let solution = solve(fen, 2, true).unwrap();
+        assert_eq!(solution.evaluation, Evaluation::PlayerHasCheckmateIn(2));
+        assert!(ends_in_checkmate(fen, &solution));
+    }
+
+    #[test]
+    fn solver_reads_mate_limit_and_checks_only_from_options() {
+        let mut limits = SearchLimits::new();
+        limits.set_mate(Some(2));
+        let mut options = Options::new(Difficulty::NORMAL, 4);
+        options.set_search_control(SearchControl::new(limits, CancellationToken::new()));
+
+        let board = Converter::convert_string_to_board("7k/8/5K2/8/8/8/8/6R1 w - - 0 1".to_string());
+        let mut mate_solver = MateSolver::new();
+        let moves = mate_solver.get_recommended_moves(&board, options.clone());
+        assert_eq!(moves.len(), 1);
+        assert_eq!(Converter::convert_move_to_string(&moves[0].chess_move), "f6f7");
+        let ponder_move = mate_solver.get_ponder_move(&board, &moves[0].chess_move).unwrap();
+        assert_eq!(Converter::convert_move_to_string(&ponder_move), "h8h7");
+
+        options.set_mate_search_checks_only(true);
+        assert!(mate_solver.get_recommended_moves(&board, options).is_empty());
+    }
+}
@@ -1,3 +1,5 @@
 
 pub mod negamax;
-mod recursive_negamax_task;
\ No newline at end of file
+pub mod mate_solver;
+mod recursive_negamax_task;
+mod transposition_table;
\ No newline at end of file
@@ -1,45 +1,442 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
 use log::{debug, info};
 use crate::datamodel::board::Board;
 use crate::datamodel::chess_move::ChessMove;
 use crate::datamodel::options::Options;
+use crate::datamodel::zobrist::Zobrist;
+use crate::evaluation::Evaluator;
 use crate::move_provider::MoveProvider;
+use crate::move_provider::scored_move::ScoredMove;
+use crate::move_provider::syzygy_tablebases::SyzygyTablebases;
 use crate::rules::piece_rule::PieceRule;
 use crate::rules::RulesProvider;
 
+/// Minimum depth of an iteration that is searched within an aspiration window
+const ASPIRATION_MIN_DEPTH: u32 = 4;
+/// Initial distance of the aspiration window bounds from the score of the previous iteration
+const ASPIRATION_WINDOW: i32 = 50;
+/// Confidence of a score the search found exactly
+const EXACT_SCORE_CONFIDENCE: f64 = 0.8;
+/// Confidence of a score the search only knows an upper bound of
+const BOUND_SCORE_CONFIDENCE: f64 = 0.4;
+
+/// An implementation of MoveProvider that searches the position with alpha beta pruning.
+///
+/// The search runs lazy smp style: the main thread and the helper threads search the same root
+/// position at staggered depths and share their results through the transposition table.
 #[derive(Debug)]
-pub struct Negamax;
+pub struct Negamax<E: Evaluator> {
+    evaluator: E,
+    /// The principal variation following each move of the last search
+    principal_variations: Vec<(ChessMove, Vec<ChessMove>)>,
+    transposition_table: TranspositionTable,
+    /// The tablebases that are probed at the inner nodes of the search
+    tablebases: SyzygyTablebases,
+}
+
+impl<E: Evaluator> Negamax<E> {
+    pub fn new(evaluator: E) -> Negamax<E> {
+        Negamax {
+            evaluator,
+            principal_variations: Vec::new(),
+            transposition_table: TranspositionTable::new(),
+            tablebases: SyzygyTablebases::new(),
+        }
+    }
+}
 
-use rayon::prelude::*;
 use crate::converter::converter::Converter;
-use crate::move_provider::negamax::recursive_negamax_task::{recursive_negamax_task, Evaluation, NegamaxTaskContext};
+use crate::move_provider::negamax::recursive_negamax_task::{search_root, NegamaxTaskContext, Evaluation, RootMove, RootSearchResult, INFINITY, MATE_BOUND};
+use crate::move_provider::negamax::transposition_table::TranspositionTable;
 
-impl MoveProvider for Negamax {
-    fn get_recommended_moves(&mut self, board: &Board, options: Options) -> Vec<ChessMove> {
+impl<E: Evaluator> MoveProvider for Negamax<E> {
+    fn get_recommended_moves(&mut self, board: &Board, options: Options) -> Vec<ScoredMove> {
         info!("Starting calculation of negamax for position {:?}.", Converter::convert_board_to_string(board));
         let moves = PieceRule::get_legal_moves(board, &board.next_color());
 
         info!("Got legal moves: {:?}", &moves);
 
-        let mut evaluated_moves: Vec<(ChessMove, Evaluation)> =
-            moves.par_iter().map( |mov|
-                (mov.clone(), recursive_negamax_task(
-            NegamaxTaskContext {
-                        board: board.clone(),
-                        chess_move: *mov,
-                        current_level: 1,
-                        player_color: board.next_color(),
-                        max_level: options.recursion_depth() as i32,
-                    }
-                ))
-            ).collect();
+        if moves.is_empty() {
+            return Vec::new();
+        }
+
+        self.evaluator.configure(&options);
+        self.tablebases.configure(&options);
+
+        let max_depth = options.search_control().limits().depth().unwrap_or(options.recursion_depth());
+        let multi_pv = options.multi_pv() as usize;
+        let threads = options.threads().max(1);
+        let root_moves: Vec<RootMove> = moves.into_iter().map(RootMove::new).collect();
+
+        let options = &options;
+        let transposition_table = &self.transposition_table;
+        let evaluator = &self.evaluator;
+        let tablebases = &self.tablebases;
+        let helpers_stop = AtomicBool::new(false);
+        let helpers_stop = &helpers_stop;
+
+        let root_moves = thread::scope(|scope| {
+            for helper_index in 1..threads {
+                // helpers start with the root moves in another order and at another depth,
+                // so they fill the transposition table with different positions
+                let mut helper_moves = root_moves.clone();
+                let helper_moves_len = helper_moves.len();
+                helper_moves.rotate_left(helper_index as usize % helper_moves_len);
+                let start_depth = 1 + helper_index % 2;
+
+                scope.spawn(move || {
+                    let mut context = NegamaxTaskContext::new(transposition_table, evaluator, options, tablebases, helpers_stop);
+                    iterative_deepening(&mut context, board, helper_moves, start_depth, max_depth, 1, false);
+                    debug!("Helper thread [{}] searched [{}] nodes", helper_index, context.nodes);
+                });
+            }
+
+            let mut context = NegamaxTaskContext::new(transposition_table, evaluator, options, tablebases, helpers_stop);
+            let root_moves = iterative_deepening(&mut context, board, root_moves, 1, max_depth, multi_pv, true);
+            helpers_stop.store(true, Ordering::Relaxed);
+            debug!("Main thread searched [{}] nodes", context.nodes);
+            root_moves
+        });
 
         debug!("Calculated negamax moves");
 
-        // sort the moves based on eval
-        evaluated_moves.sort_by_key(|(_, eval)| eval.clone());
-        debug!("Evaluation by negamax: {:?}", evaluated_moves);
-        // discard the eval and put the moves into vector
-        evaluated_moves.into_iter().map(|(mov, _)| mov).rev().collect()
+        debug!("Evaluation by negamax: {:?}", root_moves);
+        self.principal_variations = root_moves.iter()
+            .map(|root_move| (root_move.chess_move, root_move.principal_variation.clone()))
+            .collect();
+        // the first multi_pv moves have exact scores, the scores of the others are upper bounds.
+        // moves the search was stopped before are left out, only the first move is always kept
+        root_moves.into_iter().enumerate()
+            .filter(|(index, root_move)| *index == 0 || root_move.score > -INFINITY)
+            .map(|(index, root_move)| {
+                let confidence = if root_move.score == -INFINITY {
+                    // the search was stopped before the move was searched
+                    0.0
+                } else if index < multi_pv {
+                    EXACT_SCORE_CONFIDENCE
+                } else {
+                    BOUND_SCORE_CONFIDENCE
+                };
+                ScoredMove::new(root_move.chess_move, score_of_root_move(&root_move), confidence, "Negamax")
+                    .with_principal_variation(root_move.principal_variation)
+            })
+            .collect()
+
+    }
+
+    fn get_ponder_move(&self, board: &Board, chess_move: &ChessMove) -> Option<ChessMove> {
+        self.principal_variations.iter()
+            .find(|(mov, _)| mov == chess_move)
+            .and_then(|(_, principal_variation)| principal_variation.first().copied())
+            // the principal variation is cut off at positions found in the transposition table
+            .or_else(|| {
+                let mut new_board = board.clone();
+                new_board.play_move(chess_move);
+                self.transposition_table.probe(Zobrist::hash(&new_board))
+                    .and_then(|entry| entry.best_move)
+            })
+    }
+
+    fn new_game(&mut self) {
+        // the positions of the previous game will hardly come up again
+        info!("Clearing the transposition table for the new game");
+        self.transposition_table.clear();
+        self.principal_variations.clear();
+    }
+}
+
+/// Deepens the search iteratively, so there is a result if the search is stopped early.
+/// Returns the root moves sorted by the last complete iteration, best move first.
+fn iterative_deepening<E: Evaluator>(
+    context: &mut NegamaxTaskContext<E>,
+    board: &Board,
+    mut root_moves: Vec<RootMove>,
+    start_depth: u32,
+    max_depth: u32,
+    multi_pv: usize,
+    report: bool,
+) -> Vec<RootMove> {
+    for depth in start_depth..=max_depth {
+        let mut iteration = root_moves.clone();
+        let is_complete = search_with_aspiration(context, board, &mut iteration, depth, multi_pv, root_moves[0].score);
+
+        // an interrupted iteration is incomplete, so keep the result of the previous depth
+        if !is_complete {
+            info!("Search stopped during depth [{}]", depth);
+            if depth == start_depth {
+                root_moves = iteration;
+            }
+            break;
+        }
+
+        root_moves = iteration;
+        debug!("Finished negamax depth [{}]", depth);
+
+        if report {
+            report_best_lines(depth, &root_moves, multi_pv);
+        }
+    }
+
+    context.evaluator.finish_search();
+    root_moves
+}
+
+/// Searches the root moves within a narrow window around the score of the previous iteration,
+/// which cuts off more lines than a full window. The window is widened until the score lies within it.
+/// Returns false if the search was stopped before the iteration was complete.
+fn search_with_aspiration<E: Evaluator>(
+    context: &mut NegamaxTaskContext<E>,
+    board: &Board,
+    root_moves: &mut [RootMove],
+    depth: u32,
+    multi_pv: usize,
+    previous_score: i32,
+) -> bool {
+    // mate scores are exact distances, a window around them does not help
+    let mut window = if depth >= ASPIRATION_MIN_DEPTH && previous_score.abs() < MATE_BOUND {
+        (previous_score - ASPIRATION_WINDOW, previous_score + ASPIRATION_WINDOW)
+    } else {
+        (-INFINITY, INFINITY)
+    };
+    let mut delta = ASPIRATION_WINDOW;
+
+    loop {
+        match search_root(context, board, root_moves, depth as i32, multi_pv, window) {
+            RootSearchResult::Complete => return true,
+            RootSearchResult::Stopped => return false,
+            RootSearchResult::FailedLow => {
+                delta *= 2;
+                window.0 = widen_bound(window.0 - delta);
+                debug!("Aspiration search of depth [{}] failed low, new window [{:?}]", depth, window);
+            }
+            RootSearchResult::FailedHigh => {
+                delta *= 2;
+                window.1 = widen_bound(window.1 + delta);
+                debug!("Aspiration search of depth [{}] failed high, new window [{:?}]", depth, window);
+            }
+        }
+    }
+}
+
+/// Returns the expected result of a root move for the player to move. A found mate decides the game.
+fn score_of_root_move(root_move: &RootMove) -> f64 {
+    if root_move.score == -INFINITY {
+        return 0.0;
+    }
+    match root_move.evaluation {
+        Evaluation::Rating(rating) => ScoredMove::score_of_centipawns(rating),
+        Evaluation::PlayerHasCheckmateIn(_) => 1.0,
+        Evaluation::OpponentHasCheckmateIn(_) => 0.0,
+        Evaluation::Draw => 0.5,
+    }
+}
+
+/// Opens the window completely once a bound reaches the mate scores
+fn widen_bound(bound: i32) -> i32 {
+    if bound.abs() >= MATE_BOUND {
+        bound.signum() * INFINITY
+    } else {
+        bound
+    }
+}
+
+/// Sends the best lines of a finished iteration to the gui, best line first.
+fn report_best_lines(depth: u32, root_moves: &[RootMove], multi_pv: usize) {
+    for (index, root_move) in root_moves.iter().take(multi_pv).enumerate() {
+        let line: Vec<String> = std::iter::once(&root_move.chess_move).chain(root_move.principal_variation.iter())
+            .map(Converter::convert_move_to_string)
+            .collect();
+        println!("info depth {} multipv {} score {} pv {}", depth, index + 1, root_move.evaluation.to_uci_score(), line.join(" "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datamodel::enums::difficulty::Difficulty;
+    use crate::evaluation::classical_evaluator::ClassicalEvaluator;
+    use crate::move_provider::negamax::recursive_negamax_task::MATE_SCORE;
+
+    fn chess_move(uci: &str) -> ChessMove {
+        Converter::convert_string_to_move(&uci.to_string())
+    }
+
+    fn options(null_move_pruning: bool, late_move_reductions: bool, check_extensions: bool) -> Options {
+        let mut options = Options::new(Difficulty::NORMAL, 1);
+        options.set_null_move_pruning(null_move_pruning);
+        options.set_late_move_reductions(late_move_reductions);
+        options.set_check_extensions(check_extensions);
+        options
+    }
+
+    /// Every combination of null move pruning, late move reductions and check extensions
+    fn all_flags() -> Vec<(bool, bool, bool)> {
+        let mut all_flags = Vec::new();
+        for null_move_pruning in [false, true] {
+            for late_move_reductions in [false, true] {
+                for check_extensions in [false, true] {
+                    all_flags.push((null_move_pruning, late_move_reductions, check_extensions));
+                }
+            }
+        }
+        all_flags
+    }
+
+    fn root_moves(board: &Board) -> Vec<RootMove> {
+        PieceRule::get_legal_moves(board, &board.next_color()).into_iter().map(RootMove::new).collect()
+    }
+
+    /// Searches the position to the given depth with a fresh transposition table and returns the best root move
+    fn search(fen: &str, depth: u32, options: &Options) -> RootMove {
+        let board = Converter::convert_string_to_board(fen.to_string());
+        let evaluator = ClassicalEvaluator::new();
+        let transposition_table = TranspositionTable::new();
+        let tablebases = SyzygyTablebases::new();
+        let stop = AtomicBool::new(false);
+        let mut context = NegamaxTaskContext::new(&transposition_table, &evaluator, options, &tablebases, &stop);
+
+        iterative_deepening(&mut context, &board, root_moves(&board), 1, depth, 1, false).remove(0)
+    }
+
+    /// Searches a single iteration with a fresh transposition table, within an aspiration window
+    /// around the given score or with the full window
+    fn search_iteration(fen: &str, depth: u32, previous_score: Option<i32>) -> RootMove {
+        let board = Converter::convert_string_to_board(fen.to_string());
+        let options = options(false, false, false);
+        let evaluator = ClassicalEvaluator::new();
+        let transposition_table = TranspositionTable::new();
+        let tablebases = SyzygyTablebases::new();
+        let stop = AtomicBool::new(false);
+        let mut context = NegamaxTaskContext::new(&transposition_table, &evaluator, &options, &tablebases, &stop);
+
+        let mut root_moves = root_moves(&board);
+        match previous_score {
+            Some(previous_score) => assert!(search_with_aspiration(&mut context, &board, &mut root_moves, depth, 1, previous_score)),
+            None => assert_eq!(search_root(&mut context, &board, &mut root_moves, depth as i32, 1, (-INFINITY, INFINITY)), RootSearchResult::Complete),
+        }
+        root_moves.remove(0)
+    }
+
+    /// Plain negamax without any pruning, which scores the leaves like the search
+    fn minimax(evaluator: &ClassicalEvaluator, board: &Board, depth: i32, ply: i32) -> i32 {
+        let color = board.next_color();
+        let in_check = PieceRule::is_checked(board, &color);
+        if depth <= 0 && !in_check {
+            return evaluator.evaluate(board);
+        }
+        let moves = PieceRule::get_legal_moves(board, &color);
+        if moves.is_empty() {
+            return if in_check { -MATE_SCORE + ply } else { 0 };
+        }
+        if depth <= 0 {
+            return evaluator.evaluate(board);
+        }
+        moves.iter()
+            .map(|chess_move| {
+                let mut new_board = board.clone();
+                new_board.play_move(chess_move);
+                -minimax(evaluator, &new_board, depth - 1, ply + 1)
+            })
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn finds_mate_in_one_with_all_options() {
+        let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4";
+        for flags in all_flags() {
+            let best = search(fen, 3, &options(flags.0, flags.1, flags.2));
+            assert_eq!(best.chess_move, chess_move("h5f7"), "{:?}", flags);
+            assert_eq!(best.score, MATE_SCORE - 1, "{:?}", flags);
+            assert_eq!(best.evaluation, Evaluation::PlayerHasCheckmateIn(1));
+        }
+    }
+
+    #[test]
+    fn finds_mate_in_two_with_all_options() {
+        // the rooks walk the king up the board
+        let fen = "7k/8/8/8/8/8/R7/1R4K1 w - - 0 1";
+        for flags in all_flags() {
+            let best = search(fen, 4, &options(flags.0, flags.1, flags.2));
+            assert_eq!(best.score, MATE_SCORE - 3, "{:?}", flags);
+            assert_eq!(best.evaluation, Evaluation::PlayerHasCheckmateIn(2));
+        }
+    }
+
+    #[test]
+    fn finds_knight_fork_with_all_options() {
+        // the knight checks the king and attacks the queen
+        let fen = "q3k3/8/8/1N6/8/8/8/4K3 w - - 0 1";
+        for flags in all_flags() {
+            let best = search(fen, 4, &options(flags.0, flags.1, flags.2));
+            assert_eq!(best.chess_move, chess_move("b5c7"), "{:?}", flags);
+            assert!(best.score > 0, "{:?}", flags);
+        }
+    }
+
+    #[test]
+    fn null_move_pruning_stays_off_in_pawn_endings() {
+        // mutual zugzwang: whoever has to move gives way to the other king, so passing would be wrong
+        let fen = "8/8/3k4/3p4/3P4/3K4/8/8 w - - 0 1";
+
+        for (late_move_reductions, check_extensions) in [(false, false), (true, false), (false, true), (true, true)] {
+            let without = search(fen, 6, &options(false, late_move_reductions, check_extensions));
+            let with = search(fen, 6, &options(true, late_move_reductions, check_extensions));
+            assert_eq!(with.chess_move, without.chess_move);
+            assert_eq!(with.score, without.score);
+            assert_eq!(with.principal_variation, without.principal_variation);
+        }
+    }
+
+    #[test]
+    fn principal_variation_search_scores_like_plain_negamax() {
+        for fen in [
+            "q3k3/8/8/1N6/8/8/8/4K3 w - - 0 1",
+            "6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1",
+            "6k1/5ppp/8/3n4/8/2N5/5PPP/3R2K1 w - - 0 1",
+        ] {
+            let board = Converter::convert_string_to_board(fen.to_string());
+            let evaluator = ClassicalEvaluator::new();
+            let expected = PieceRule::get_legal_moves(&board, &board.next_color()).iter()
+                .map(|chess_move| {
+                    let mut new_board = board.clone();
+                    new_board.play_move(chess_move);
+                    -minimax(&evaluator, &new_board, 2, 1)
+                })
+                .max()
+                .unwrap();
+
+            assert_eq!(search_iteration(fen, 3, None).score, expected, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn aspiration_window_gives_full_window_result() {
+        let fen = "6k1/5ppp/8/3n4/8/2N5/5PPP/3R2K1 w - - 0 1";
+        let full_window = search_iteration(fen, 4, None);
+
+        // the window fails low, fits and fails high
+        for previous_score in [full_window.score + 400, full_window.score, full_window.score - 400] {
+            let aspiration = search_iteration(fen, 4, Some(previous_score));
+            assert_eq!(aspiration.chess_move, full_window.chess_move, "{}", previous_score);
+            assert_eq!(aspiration.score, full_window.score, "{}", previous_score);
+        }
+    }
+
+    #[test]
+    fn aspiration_window_is_widened_to_mate_scores() {
+        let fen = "7k/8/8/8/8/8/R7/1R4K1 w - - 0 1";
+        let aspiration = search_iteration(fen, 4, Some(0));
+        assert_eq!(aspiration.score, MATE_SCORE - 3);
+        assert_eq!(aspiration.evaluation, Evaluation::PlayerHasCheckmateIn(2));
+    }
 
+    #[test]
+    fn widen_bound_opens_window_at_mate_scores() {
+        assert_eq!(widen_bound(-150), -150);
+        assert_eq!(widen_bound(MATE_BOUND - 1), MATE_BOUND - 1);
+        assert_eq!(widen_bound(MATE_BOUND), INFINITY);
+        assert_eq!(widen_bound(-MATE_BOUND - 10), -INFINITY);
     }
-}
\ No newline at end of file
+}
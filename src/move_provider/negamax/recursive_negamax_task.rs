@@ -1,20 +1,96 @@
+use std::cmp::Reverse;
+use std::sync::atomic::{AtomicBool, Ordering};
 use crate::datamodel::board::Board;
 use crate::datamodel::chess_move::ChessMove;
-use crate::datamodel::enums::color::Color;
-
-
+use crate::datamodel::enums::file::File;
+use crate::datamodel::enums::piece_type::PieceType;
+use crate::datamodel::enums::rank::Rank;
 use crate::datamodel::field::Field;
+use crate::datamodel::zobrist::Zobrist;
+use crate::move_provider::negamax::transposition_table::{Bound, TableEntry, TranspositionTable};
+use crate::datamodel::enums::color::Color;
+use crate::datamodel::options::Options;
+use crate::evaluation::Evaluator;
+use crate::move_provider::syzygy_tablebases::SyzygyTablebases;
 use crate::rules::piece_rule::PieceRule;
 use crate::rules::RulesProvider;
+use shakmaty_syzygy::Wdl;
+
+/// Score of being checkmated at the root. A checkmate n plies away from the root scores
+/// MATE_SCORE - n for the player who mates.
+pub const MATE_SCORE: i32 = 100_000;
+/// Scores beyond this bound are checkmates
+pub const MATE_BOUND: i32 = MATE_SCORE - 1_000;
+/// Score that is better than any reachable score
+pub const INFINITY: i32 = MATE_SCORE + 1;
+const DRAW_SCORE: i32 = 0;
+/// Number of nodes that are searched between two checks whether the search has to stop
+const NODES_BETWEEN_STOP_CHECKS: u64 = 1024;
+/// Maximum distance from the root, which bounds the search extensions
+const MAX_PLY: i32 = 64;
+/// Score of a position the tablebases prove won. It lies below the mate scores, as the tablebases
+/// only know the distance to zeroing, and is lowered by the ply to prefer the shorter way into the tables.
+const TABLEBASE_WIN: i32 = MATE_BOUND - MAX_PLY;
+/// Scores beyond this bound are tablebase wins or checkmates, which both depend on the ply
+const TABLEBASE_BOUND: i32 = TABLEBASE_WIN - MAX_PLY;
+/// Minimum remaining depth for null move pruning
+const NULL_MOVE_MIN_DEPTH: i32 = 3;
+/// Minimum remaining depth for late move reductions
+const LATE_MOVE_MIN_DEPTH: i32 = 3;
+/// Number of moves that are searched at full depth before the late moves are reduced
+const LATE_MOVE_MIN_INDEX: usize = 3;
+
+/// The state of a single search thread. All search threads share the transposition table.
+pub struct NegamaxTaskContext<'a, E: Evaluator> {
+    pub transposition_table: &'a TranspositionTable,
+    pub evaluator: &'a E,
+    pub options: &'a Options,
+    pub tablebases: &'a SyzygyTablebases,
+    /// Set when the main thread finished its search, so the helper threads stop as well
+    pub helpers_stop: &'a AtomicBool,
+    pub nodes: u64,
+    pub stopped: bool,
+    /// The ply of the position reached by the last null move, two null moves in a row are pointless
+    null_move_ply: Option<i32>,
+}
 
-use rayon::prelude::*;
+impl<'a, E: Evaluator> NegamaxTaskContext<'a, E> {
+    pub fn new(transposition_table: &'a TranspositionTable, evaluator: &'a E, options: &'a Options, tablebases: &'a SyzygyTablebases, helpers_stop: &'a AtomicBool) -> Self {
+        NegamaxTaskContext {
+            transposition_table,
+            evaluator,
+            options,
+            tablebases,
+            helpers_stop,
+            nodes: 0,
+            stopped: false,
+            null_move_ply: None,
+        }
+    }
 
-pub struct NegamaxTaskContext {
-    pub board: Board,
+    fn should_stop(&self) -> bool {
+        self.options.search_control().should_stop() || self.helpers_stop.load(Ordering::Relaxed)
+    }
+}
+
+/// A move at the root of the search with its evaluation and the principal variation following it.
+#[derive(Clone, Debug)]
+pub struct RootMove {
     pub chess_move: ChessMove,
-    pub current_level: i32,
-    pub player_color: Color,
-    pub max_level: i32,
+    pub score: i32,
+    pub evaluation: Evaluation,
+    pub principal_variation: Vec<ChessMove>,
+}
+
+impl RootMove {
+    pub fn new(chess_move: ChessMove) -> RootMove {
+        RootMove {
+            chess_move,
+            score: -INFINITY,
+            evaluation: Evaluation::Rating(0),
+            principal_variation: Vec::new(),
+        }
+    }
 }
 
 #[derive(Eq, PartialEq, Clone, Debug)]
@@ -26,49 +102,27 @@ pub enum Evaluation {
 }
 
 impl Evaluation {
-    pub fn negate(&self) -> Evaluation {
-        match self {
-            Evaluation::Rating(rating) => Evaluation::Rating(-*rating),
-            Evaluation::OpponentHasCheckmateIn(n) => Evaluation::PlayerHasCheckmateIn(*n),
-            Evaluation::PlayerHasCheckmateIn(n) => Evaluation::OpponentHasCheckmateIn(*n),
-            Evaluation::Draw => Evaluation::Draw,
+    /// Converts a score of the search, which is relative to the player to move at the root,
+    /// into an evaluation.
+    pub fn from_score(score: i32) -> Evaluation {
+        if score > MATE_BOUND {
+            // the player mates with his n-th move, which is ply 2n-1
+            Evaluation::PlayerHasCheckmateIn((MATE_SCORE - score + 1) / 2)
+        } else if score < -MATE_BOUND {
+            // the opponent mates with his n-th move, which is ply 2n
+            Evaluation::OpponentHasCheckmateIn((MATE_SCORE + score) / 2)
+        } else {
+            Evaluation::Rating(score)
         }
     }
 
-    // our naive guess has to be updated with the opponents best response
-    // our move is only good, if opponent does not have a good response
-    pub fn update_with_opponents_best_response(self, opponent_move: &Self) -> Self {
-        // if we only have a estimated rating, update it with the evaluation of the best response
-        if let Evaluation::Rating(rating) = self {
-            match opponent_move {
-                // e.g. if the opponent has a move that gains a rook (+5 for them)
-                // after we take a bishop (+3 for us), that gives a total eval of -2 for us
-                Evaluation::Rating(opponent_rating) => {
-                    Evaluation::Rating(rating-opponent_rating)
-                },
-
-                // if opponents opponent has mate in n moves after the move
-                // that means we have mate in n+1 moves
-                // e.g. after our candidate move, our opponent evaluates the position as
-                // "my opponent (that is us) has mate in one, no matter what i do"
-                // then that means if we play the move in question, we have forced mate in two
-                Evaluation::OpponentHasCheckmateIn(n) => {
-                    Evaluation::PlayerHasCheckmateIn(*n+1)
-                }
-                // if opponent has mate in n after the move
-                // opponent has mate in n after the move
-                Evaluation::PlayerHasCheckmateIn(n) => {
-                    Evaluation::OpponentHasCheckmateIn(*n)
-                }
-                // if our opponent is sure its a forced draw, then it is a forced draw
-                Evaluation::Draw => {
-                    Evaluation::Draw
-                }
-            }
-        } else {
-            // if we have a more concrete evaluation of the position
-            // (forced mate, forced draw) just keep that.
-            self
+    /// Returns the evaluation as score of the uci info command.
+    pub fn to_uci_score(&self) -> String {
+        match self {
+            Evaluation::Rating(rating) => format!("cp {}", rating),
+            Evaluation::PlayerHasCheckmateIn(n) => format!("mate {}", n),
+            Evaluation::OpponentHasCheckmateIn(n) => format!("mate -{}", n),
+            Evaluation::Draw => "cp 0".to_string(),
         }
     }
 }
@@ -138,83 +192,360 @@ impl Ord for Evaluation {
     }
 }
 
-pub fn recursive_negamax_task(context: NegamaxTaskContext) -> Evaluation {
-    let mut new_board = context.board.clone();
-    let opponent_color: Color = match context.player_color {
-        Color::BLACK => Color::WHITE,
-        Color::WHITE => Color::BLACK,
+/// The outcome of searching the root moves within a window.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RootSearchResult {
+    /// The best score lies within the window
+    Complete,
+    /// The search was stopped before the iteration was complete
+    Stopped,
+    /// The best score is at most the lower end of the window
+    FailedLow,
+    /// The best score is at least the upper end of the window
+    FailedHigh,
+}
+
+/// Searches all root moves to the given depth and sorts them by their score, best first.
+/// The first multi_pv moves get exact scores, the scores of the other moves are upper bounds.
+///
+/// The best line is searched within the given window (alpha, beta). If its score lies outside
+/// of the window, the search returns early and has to be repeated with a wider window.
+pub fn search_root<E: Evaluator>(
+    context: &mut NegamaxTaskContext<E>,
+    board: &Board,
+    root_moves: &mut [RootMove],
+    depth: i32,
+    multi_pv: usize,
+    window: (i32, i32),
+) -> RootSearchResult {
+    let multi_pv = multi_pv.min(root_moves.len());
+    context.evaluator.start_search(board);
+
+    // every pass finds the best of the moves that are not yet part of the reported lines
+    for pv_index in 0..multi_pv {
+        // the other lines are searched with an open window
+        let (window_alpha, beta) = if pv_index == 0 { window } else { (-INFINITY, INFINITY) };
+        let mut alpha = window_alpha;
+
+        for index in pv_index..root_moves.len() {
+            let mut new_board = board.clone();
+            let changes = new_board.play_move_with_changes(&root_moves[index].chess_move);
+
+            let mut principal_variation = Vec::new();
+            context.evaluator.move_played(&changes);
+            let score = principal_variation_search(context, &new_board, depth - 1, 1, alpha, beta, index == pv_index, &mut principal_variation);
+            context.evaluator.move_undone();
+            if context.stopped {
+                return RootSearchResult::Stopped;
+            }
+
+            let root_move = &mut root_moves[index];
+            root_move.score = score;
+            root_move.evaluation = evaluate_root_move(&new_board, score);
+
+            if score > alpha {
+                alpha = score;
+                root_move.principal_variation = principal_variation;
+                // move the best move of this pass to the front of the remaining moves
+                root_moves[pv_index..=index].rotate_right(1);
+
+                if score >= beta {
+                    return RootSearchResult::FailedHigh;
+                }
+            }
+        }
+
+        if alpha == window_alpha && window_alpha > -INFINITY {
+            return RootSearchResult::FailedLow;
+        }
+    }
+
+    root_moves[multi_pv..].sort_by_key(|root_move| Reverse(root_move.score));
+    RootSearchResult::Complete
+}
+
+/// Searches the move that led to the given board for the player who made it.
+///
+/// The first move of a node is expected to be the best, so it is searched with the full window.
+/// The other moves only have to be proven worse, which a null window does cheaper. If such a move
+/// turns out better after all, it is searched again with the full window.
+#[allow(clippy::too_many_arguments)]
+fn principal_variation_search<E: Evaluator>(
+    context: &mut NegamaxTaskContext<E>,
+    board: &Board,
+    depth: i32,
+    ply: i32,
+    alpha: i32,
+    beta: i32,
+    is_first_move: bool,
+    principal_variation: &mut Vec<ChessMove>,
+) -> i32 {
+    if is_first_move {
+        return -recursive_negamax_task(context, board, depth, ply, -beta, -alpha, principal_variation);
+    }
+
+    let score = -recursive_negamax_task(context, board, depth, ply, -alpha - 1, -alpha, principal_variation);
+    if score > alpha && score < beta && !context.stopped {
+        -recursive_negamax_task(context, board, depth, ply, -beta, -alpha, principal_variation)
+    } else {
+        score
+    }
+}
+
+/// Searches the position on the board with alpha beta pruning.
+/// Returns the score of the position for the player to move and fills the principal variation.
+pub fn recursive_negamax_task<E: Evaluator>(
+    context: &mut NegamaxTaskContext<E>,
+    board: &Board,
+    depth: i32,
+    ply: i32,
+    mut alpha: i32,
+    beta: i32,
+    principal_variation: &mut Vec<ChessMove>,
+) -> i32 {
+    principal_variation.clear();
+
+    context.nodes += 1;
+    if context.nodes.is_multiple_of(NODES_BETWEEN_STOP_CHECKS) && context.should_stop() {
+        context.stopped = true;
+    }
+    // the result of an interrupted search is discarded, so just return anything
+    if context.stopped {
+        return DRAW_SCORE;
+    }
+
+    let color = board.next_color();
+    let in_check = PieceRule::is_checked(board, &color);
+
+    // search forcing lines deeper
+    let depth = if in_check && context.options.check_extensions() && ply < MAX_PLY {
+        depth + 1
+    } else {
+        depth
     };
-    
-    
-    // rate the move for the given position by itself (without calculating more than the one move
-    // into the future) and play the move
-    let evaluation: Evaluation = rate_move(&mut new_board, &context.chess_move, context.player_color);
-
-    // if we reached max recursion level or if we have a concrete eval, return the evaluation
-    if context.current_level >= context.max_level
-        || !matches!(evaluation, Evaluation::Rating(_))
+
+    // at the end of the search only look for checkmate, if the player is in check
+    if (depth <= 0 && !in_check) || ply >= MAX_PLY {
+        return context.evaluator.evaluate(board);
+    }
+
+    let hash = Zobrist::hash(board);
+    let table_entry = context.transposition_table.probe(hash);
+    if let Some(entry) = table_entry {
+        if entry.depth >= depth {
+            let score = score_from_table(entry.score, ply);
+            let is_usable = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => score >= beta,
+                Bound::Upper => score <= alpha,
+            };
+            if is_usable {
+                return score;
+            }
+        }
+    }
+
+    // the tablebases know the result of the position without searching it
+    if let Some(wdl) = context.tablebases.probe_wdl(board) {
+        return match wdl {
+            Wdl::Win => TABLEBASE_WIN - ply,
+            Wdl::CursedWin => DRAW_SCORE + 1,
+            Wdl::Draw => DRAW_SCORE,
+            Wdl::BlessedLoss => DRAW_SCORE - 1,
+            Wdl::Loss => -TABLEBASE_WIN + ply,
+        };
+    }
+
+    let legal_moves = PieceRule::get_legal_moves(board, &color);
+    if legal_moves.is_empty() {
+        // checkmate or stalemate
+        return if in_check { -MATE_SCORE + ply } else { DRAW_SCORE };
+    }
+
+    if depth <= 0 {
+        return context.evaluator.evaluate(board);
+    }
+
+    // null move pruning: if passing the turn still fails high, a real move will as well.
+    // this does not hold in zugzwang, which is likely in pawn endings and when in check.
+    let after_null_move = context.null_move_ply == Some(ply);
+    if context.options.null_move_pruning()
+        && depth >= NULL_MOVE_MIN_DEPTH
+        && !in_check
+        && !after_null_move
+        && beta.abs() < MATE_BOUND
+        && has_non_pawn_material(board, color)
+        && context.evaluator.evaluate(board) >= beta
     {
-        return evaluation;
-    }
-
-    // if we only have a estimated rating of the move, recursively check the possible responses
-    let legal_moves = PieceRule::get_legal_moves(&new_board, &new_board.next_color());
-
-    // for each response move compute the rating
-    legal_moves.par_iter().map( |legal_move|
-         recursive_negamax_task(NegamaxTaskContext {
-            board: new_board.clone(),
-            chess_move: *legal_move,
-            current_level: context.current_level + 1,
-            player_color: opponent_color,
-             ..context
-        })
-    // update our evaluation with the best response
-    // if there is no legal move for the opponent, its stalemate or checkmate
-    // since checkmate is handled above, it is fine to return draw here for now as default
-    ).max().map_or(Evaluation::Draw, |best_response|
-        evaluation.update_with_opponents_best_response(&best_response))
-}
-
-/// rates the given move for the player of the given color
-fn rate_move(board: &mut Board, chess_move: &ChessMove, player_color: Color) -> Evaluation {
-    let mut rating: i32 = 0;
-    let to_field: Field = *chess_move.to_field();
-
-    let opponent_color: Color = match player_color {
-        Color::BLACK => Color::WHITE,
-        Color::WHITE => Color::BLACK,
-    };
+        let reduction = if depth > 6 { 3 } else { 2 };
+        let mut null_board = board.clone();
+        null_board.play_null_move();
 
-    // get the piece that will get taken
-    let piece_to_hit = board.get_piece(&to_field).clone();
-
-    // play the move
-    board.play_move(chess_move);
-    
-    // if opponent is mated, player has mate in one
-    if PieceRule::is_checkmated(board, &opponent_color) {
-        return Evaluation::PlayerHasCheckmateIn(1)
-    }
-    
-    // TODO: DRAW logic here
-    
-    if let Some(piece_to_hit) = piece_to_hit {
-        // own piece being taken is bad
-        if piece_to_hit.color() == player_color {
-            rating -= piece_to_hit.piece_type().value() as i32;
+        let previous_null_move_ply = context.null_move_ply.replace(ply + 1);
+        let mut null_principal_variation = Vec::new();
+        context.evaluator.move_played(&[]);
+        let score = -recursive_negamax_task(context, &null_board, depth - 1 - reduction, ply + 1, -beta, -beta + 1, &mut null_principal_variation);
+        context.evaluator.move_undone();
+        context.null_move_ply = previous_null_move_ply;
+
+        if context.stopped {
+            return DRAW_SCORE;
+        }
+        if score >= beta {
+            // a mate found after passing is not proven
+            return beta;
+        }
+    }
+
+    let ordered_moves = order_moves(board, legal_moves, table_entry.and_then(|entry| entry.best_move), context.evaluator);
+
+    let original_alpha = alpha;
+    let mut best_score = -INFINITY;
+    let mut best_move: Option<ChessMove> = None;
+    let mut child_principal_variation: Vec<ChessMove> = Vec::new();
+
+    for (index, chess_move) in ordered_moves.into_iter().enumerate() {
+        let is_quiet = board.get_piece(chess_move.to_field()).is_none()
+            && chess_move.promote_to().is_none()
+            && !is_en_passant(board, &chess_move);
+
+        let mut new_board = board.clone();
+        let changes = new_board.play_move_with_changes(&chess_move);
+
+        // late move reductions: quiet moves that are ordered late are unlikely to be good,
+        // so they are searched with reduced depth and a null window first
+        let reduction = if context.options.late_move_reductions()
+            && depth >= LATE_MOVE_MIN_DEPTH
+            && index >= LATE_MOVE_MIN_INDEX
+            && is_quiet
+            && !in_check
+            && !PieceRule::is_checked(&new_board, &new_board.next_color())
+        {
+            if index >= 2 * LATE_MOVE_MIN_INDEX && depth >= 6 { 2 } else { 1 }
+        } else {
+            0
+        };
+
+        context.evaluator.move_played(&changes);
+        let score = if reduction > 0 {
+            let score = -recursive_negamax_task(context, &new_board, depth - 1 - reduction, ply + 1, -alpha - 1, -alpha, &mut child_principal_variation);
+            // the reduced search does not fail low, so the move has to be searched at full depth
+            if score > alpha {
+                principal_variation_search(context, &new_board, depth - 1, ply + 1, alpha, beta, false, &mut child_principal_variation)
+            } else {
+                score
+            }
         } else {
-            rating += piece_to_hit.piece_type().value() as i32;
+            principal_variation_search(context, &new_board, depth - 1, ply + 1, alpha, beta, index == 0, &mut child_principal_variation)
+        };
+        context.evaluator.move_undone();
+        if context.stopped {
+            return DRAW_SCORE;
+        }
+
+        if score > best_score {
+            best_score = score;
+            best_move = Some(chess_move);
+
+            if score > alpha {
+                alpha = score;
+                principal_variation.clear();
+                principal_variation.push(chess_move);
+                principal_variation.extend_from_slice(&child_principal_variation);
+
+                if alpha >= beta {
+                    // the opponent will avoid this position
+                    break;
+                }
+            }
         }
     }
 
-    if PieceRule::is_checked(board, &player_color) {
-        rating -= 1;
+    let bound = if best_score >= beta {
+        Bound::Lower
+    } else if best_score > original_alpha {
+        Bound::Exact
+    } else {
+        Bound::Upper
+    };
+    context.transposition_table.store(hash, TableEntry {
+        depth,
+        score: score_to_table(best_score, ply),
+        bound,
+        best_move,
+    });
+
+    best_score
+}
+
+/// Returns the evaluation of a root move from the board after the move and its score.
+/// A move that leaves the opponent without legal moves and not in check is a draw.
+fn evaluate_root_move(board: &Board, score: i32) -> Evaluation {
+    let opponent_color = board.next_color();
+    if !PieceRule::is_checked(board, &opponent_color)
+        && PieceRule::get_legal_moves(board, &opponent_color).is_empty()
+    {
+        return Evaluation::Draw;
+    }
+    Evaluation::from_score(score)
+}
+
+/// Sorts the moves so that the moves most likely to be best are searched first:
+/// the best move of the transposition table, then captures of valuable pieces by cheap pieces.
+/// The pieces have the values of the evaluator.
+fn order_moves<E: Evaluator>(board: &Board, mut moves: Vec<ChessMove>, table_move: Option<ChessMove>, evaluator: &E) -> Vec<ChessMove> {
+    moves.sort_by_cached_key(|chess_move| {
+        if table_move.is_some_and(|table_move| table_move == *chess_move) {
+            return Reverse(i32::MAX);
+        }
+
+        let mut order = 0;
+        if let Some(victim) = board.get_piece(chess_move.to_field()) {
+            let attacker_value = board.get_piece(chess_move.from_field())
+                .map_or(0, |attacker| evaluator.piece_value(attacker.piece_type()));
+            order += 100 * evaluator.piece_value(victim.piece_type()) - attacker_value;
+        }
+        if let Some(promotion) = chess_move.promote_to() {
+            order += 100 * evaluator.piece_value(*promotion);
+        }
+        Reverse(order)
+    });
+    moves
+}
+
+/// Returns whether the move captures a pawn en passant, which leaves its target field empty.
+fn is_en_passant(board: &Board, chess_move: &ChessMove) -> bool {
+    board.en_passant_field().is_some_and(|field| field == *chess_move.to_field())
+        && board.get_piece(chess_move.from_field()).is_some_and(|piece| piece.piece_type() == PieceType::PAWN)
+}
+
+/// Returns whether the player of the given color has other pieces than king and pawns.
+fn has_non_pawn_material(board: &Board, color: Color) -> bool {
+    (0..8).any(|rank| (0..8).any(|file|
+        board.get_piece(&Field::new(File::from_index(file), Rank::from_index(rank))).is_some_and(|piece|
+            piece.color() == color
+                && piece.piece_type() != PieceType::PAWN
+                && piece.piece_type() != PieceType::KING)
+    ))
+}
+
+/// Mate and tablebase scores are stored relative to the position, not to the root
+fn score_to_table(score: i32, ply: i32) -> i32 {
+    if score > TABLEBASE_BOUND {
+        score + ply
+    } else if score < -TABLEBASE_BOUND {
+        score - ply
+    } else {
+        score
     }
-    if PieceRule::is_checked(board, &opponent_color) {
-        rating += 1;
+}
+
+fn score_from_table(score: i32, ply: i32) -> i32 {
+    if score > TABLEBASE_BOUND {
+        score - ply
+    } else if score < -TABLEBASE_BOUND {
+        score + ply
+    } else {
+        score
     }
-    
-    // negamax works by returning the negative of the rating of the move
-    Evaluation::Rating(rating)
-}
\ No newline at end of file
+}
@@ -0,0 +1,226 @@
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::datamodel::chess_move::ChessMove;
+use crate::datamodel::enums::file::File;
+use crate::datamodel::enums::piece_type::PieceType;
+use crate::datamodel::enums::rank::Rank;
+use crate::datamodel::field::Field;
+
+/// Number of entries of the table. Must be a power of two.
+const TABLE_SIZE: usize = 1 << 20;
+
+/// Describes how the stored score relates to the real score of the position.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Bound {
+    /// The score is exact
+    Exact,
+    /// The real score is at least the stored score (fail high)
+    Lower,
+    /// The real score is at most the stored score (fail low)
+    Upper,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct TableEntry {
+    pub depth: i32,
+    pub score: i32,
+    pub bound: Bound,
+    pub best_move: Option<ChessMove>,
+}
+
+/// A hash table of search results that is shared between all search threads.
+///
+/// The table is lockless: each entry consists of the data and the key xor data.
+/// An entry that was torn by concurrent writes does not verify against the key and is ignored.
+pub struct TranspositionTable {
+    entries: Vec<(AtomicU64, AtomicU64)>,
+}
+
+impl TranspositionTable {
+    pub fn new() -> TranspositionTable {
+        TranspositionTable {
+            entries: (0..TABLE_SIZE).map(|_| (AtomicU64::new(0), AtomicU64::new(0))).collect(),
+        }
+    }
+
+    /// Returns the stored entry of the position with the given hash, if there is one.
+    pub fn probe(&self, hash: u64) -> Option<TableEntry> {
+        let (checksum, data) = &self.entries[hash as usize & (TABLE_SIZE - 1)];
+        let data = data.load(Ordering::Relaxed);
+
+        if data == 0 || checksum.load(Ordering::Relaxed) ^ data != hash {
+            return None;
+        }
+        Some(decode_entry(data))
+    }
+
+    /// Stores the entry of the position with the given hash.
+    /// A deeper entry of the same position is kept.
+    pub fn store(&self, hash: u64, entry: TableEntry) {
+        if self.probe(hash).is_some_and(|stored| stored.depth > entry.depth) {
+            return;
+        }
+
+        let (checksum, data) = &self.entries[hash as usize & (TABLE_SIZE - 1)];
+        let encoded = encode_entry(&entry);
+        data.store(encoded, Ordering::Relaxed);
+        checksum.store(hash ^ encoded, Ordering::Relaxed);
+    }
+
+    /// Removes all entries.
+    pub fn clear(&self) {
+        for (checksum, data) in &self.entries {
+            data.store(0, Ordering::Relaxed);
+            checksum.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Debug for TranspositionTable {
+    // the entries are far too many to be logged
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TranspositionTable")
+            .field("size", &self.entries.len())
+            .finish()
+    }
+}
+
+/// Packs an entry into 64 bits:
+/// 32 bits score, 8 bits depth, 2 bits bound, 1 bit move present and 15 bits move.
+fn encode_entry(entry: &TableEntry) -> u64 {
+    let bound: u64 = match entry.bound {
+        Bound::Exact => 1,
+        Bound::Lower => 2,
+        Bound::Upper => 3,
+    };
+    let chess_move: u64 = entry.best_move.map_or(0, |mov| (1 << 15) | encode_move(&mov));
+
+    (entry.score as u32 as u64)
+        | ((entry.depth.clamp(0, 255) as u64) << 32)
+        | (bound << 40)
+        | (chess_move << 42)
+}
+
+fn decode_entry(data: u64) -> TableEntry {
+    let chess_move = (data >> 42) & 0xFFFF;
+
+    TableEntry {
+        score: data as u32 as i32,
+        depth: ((data >> 32) & 0xFF) as i32,
+        bound: match (data >> 40) & 0b11 {
+            2 => Bound::Lower,
+            3 => Bound::Upper,
+            _ => Bound::Exact,
+        },
+        best_move: if chess_move & (1 << 15) != 0 { Some(decode_move(chess_move)) } else { None },
+    }
+}
+
+/// Packs a move into 15 bits: 6 bits from field, 6 bits to field and 3 bits promotion.
+fn encode_move(chess_move: &ChessMove) -> u64 {
+    let from = chess_move.from_field().rank().to_index() * 8 + chess_move.from_field().file().to_index();
+    let to = chess_move.to_field().rank().to_index() * 8 + chess_move.to_field().file().to_index();
+    let promotion: u64 = match chess_move.promote_to() {
+        None => 0,
+        Some(PieceType::QUEEN) => 1,
+        Some(PieceType::ROOK) => 2,
+        Some(PieceType::BISHOP) => 3,
+        Some(PieceType::KNIGHT) => 4,
+        Some(_) => 0,
+    };
+
+    from as u64 | ((to as u64) << 6) | (promotion << 12)
+}
+
+fn decode_move(data: u64) -> ChessMove {
+    let from = (data & 0x3F) as usize;
+    let to = ((data >> 6) & 0x3F) as usize;
+    let promotion = match (data >> 12) & 0b111 {
+        1 => Some(PieceType::QUEEN),
+        2 => Some(PieceType::ROOK),
+        3 => Some(PieceType::BISHOP),
+        4 => Some(PieceType::KNIGHT),
+        _ => None,
+    };
+
+    ChessMove::new(
+        Field::new(File::from_index(from % 8), Rank::from_index(from / 8)),
+        Field::new(File::from_index(to % 8), Rank::from_index(to / 8)),
+        promotion,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::converter::Converter;
+
+    fn chess_move(uci: &str) -> ChessMove {
+        Converter::convert_string_to_move(&uci.to_string())
+    }
+
+    fn assert_round_trip(entry: TableEntry) {
+        let decoded = decode_entry(encode_entry(&entry));
+        assert_eq!(decoded.score, entry.score);
+        assert_eq!(decoded.depth, entry.depth);
+        assert_eq!(decoded.bound, entry.bound);
+        assert_eq!(decoded.best_move, entry.best_move);
+    }
+
+    #[test]
+    fn entries_survive_packing() {
+        for score in [0, 1, -1, 148, -2_000, 99_950, -99_950, i32::MAX, i32::MIN] {
+            for bound in [Bound::Exact, Bound::Lower, Bound::Upper] {
+                assert_round_trip(TableEntry { depth: 7, score, bound, best_move: Some(chess_move("e2e4")) });
+                assert_round_trip(TableEntry { depth: 0, score, bound, best_move: None });
+            }
+        }
+        assert_round_trip(TableEntry { depth: 255, score: -5, bound: Bound::Upper, best_move: None });
+    }
+
+    #[test]
+    fn moves_survive_packing() {
+        for uci in ["a1a2", "h8h1", "a1h8", "h1a8", "e1g1", "e7e8q", "b2a1r", "g7g8b", "h2h1n"] {
+            let mov = chess_move(uci);
+            assert_eq!(decode_move(encode_move(&mov)), mov, "{}", uci);
+        }
+    }
+
+    #[test]
+    fn depth_is_clamped_to_eight_bits() {
+        let entry = TableEntry { depth: 300, score: 12, bound: Bound::Lower, best_move: None };
+        assert_eq!(decode_entry(encode_entry(&entry)).depth, 255);
+        let entry = TableEntry { depth: -3, score: 12, bound: Bound::Lower, best_move: None };
+        assert_eq!(decode_entry(encode_entry(&entry)).depth, 0);
+    }
+
+    #[test]
+    fn probe_finds_only_stored_positions() {
+        let table = TranspositionTable::new();
+        let hash = 0x1234_5678_9ABC_DEF0;
+        assert!(table.probe(hash).is_none());
+
+        table.store(hash, TableEntry { depth: 4, score: 30, bound: Bound::Exact, best_move: Some(chess_move("g1f3")) });
+        let entry = table.probe(hash).expect("the stored entry");
+        assert_eq!(entry.score, 30);
+        assert_eq!(entry.best_move, Some(chess_move("g1f3")));
+
+        // another position in the same slot does not verify against the checksum
+        assert!(table.probe(hash ^ (1 << 40)).is_none());
+
+        table.clear();
+        assert!(table.probe(hash).is_none());
+    }
+
+    #[test]
+    fn deeper_entries_are_kept() {
+        let table = TranspositionTable::new();
+        let hash = 42;
+        table.store(hash, TableEntry { depth: 6, score: 10, bound: Bound::Exact, best_move: None });
+        table.store(hash, TableEntry { depth: 2, score: 20, bound: Bound::Exact, best_move: None });
+        assert_eq!(table.probe(hash).map(|entry| entry.score), Some(10));
+
+        table.store(hash, TableEntry { depth: 6, score: 30, bound: Bound::Lower, best_move: None });
+        assert_eq!(table.probe(hash).map(|entry| entry.score), Some(30));
+    }
+}
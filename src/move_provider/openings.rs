@@ -1,23 +1,83 @@
 use crate::converter::converter::Converter;
 use crate::datamodel::board::Board;
 use crate::datamodel::chess_move::ChessMove;
+use crate::datamodel::enums::color::Color;
 use crate::datamodel::options::Options;
+use crate::move_provider::http_client::HttpClient;
+use crate::move_provider::polyglot_book::choose_weighted_move;
+use crate::move_provider::response_cache::{cache_key, ResponseCache};
 use crate::move_provider::MoveProvider;
+use crate::move_provider::scored_move::ScoredMove;
 
-use reqwest::blocking::get;
-use log::{ info, warn};
+use log::{debug, info, warn};
+use reqwest::Url;
 use serde::Deserialize;
 
-#[derive(Deserialize)]
+/// Base url of the Lichess opening explorer
+pub const DEFAULT_EXPLORER_URL: &str = "https://explorer.lichess.ovh";
+pub const DEFAULT_EXPLORER_DATABASE: &str = "masters";
+/// The databases of the opening explorer: games of masters, games played on Lichess
+/// and the games of a single Lichess player
+pub const EXPLORER_DATABASES: [&str; 3] = ["masters", "lichess", "player"];
+/// Maximum number of top games the opening explorer returns
+pub const MAX_EXPLORER_TOP_GAMES: u32 = 15;
+/// Maximum of the ExplorerMinGames option
+pub const MAX_EXPLORER_MIN_GAMES: u32 = 1_000_000;
+/// Quantile of the normal distribution for the confidence interval of the expected score, about 95%
+const CONFIDENCE_QUANTILE: f64 = 1.96;
+
+#[derive(Deserialize, Debug)]
 struct OpeningMove {
     pub uci: String,
-    #[serde(rename = "averageRating")]
-    pub average_rating: i32,
+    /// The player database only knows the average rating of the opponents
+    #[serde(default, rename = "averageRating", alias = "averageOpponentRating")]
+    pub average_rating: Option<i32>,
     pub white: i32,
     pub draws: i32,
     pub black: i32,
 }
 
+impl OpeningMove {
+    fn games(&self) -> u32 {
+        (self.white + self.draws + self.black).max(0) as u32
+    }
+
+    /// Returns the share of the points the player to move scored with the move.
+    fn expected_score(&self, color: Color) -> f64 {
+        let wins = if color == Color::WHITE { self.white } else { self.black };
+        (wins as f64 + self.draws as f64 / 2.0) / self.games().max(1) as f64
+    }
+
+    /// Returns the lower bound of the confidence interval of the expected score by Wilson.
+    /// The fewer games a move has, the lower the bound, so rare moves need a much better
+    /// score to be trusted.
+    fn confident_score(&self, color: Color) -> f64 {
+        let games = self.games() as f64;
+        if games == 0.0 {
+            return 0.0;
+        }
+        let score = self.expected_score(color);
+        let z_squared = CONFIDENCE_QUANTILE * CONFIDENCE_QUANTILE;
+        let center = score + z_squared / (2.0 * games);
+        let spread = CONFIDENCE_QUANTILE * (score * (1.0 - score) / games + z_squared / (4.0 * games * games)).sqrt();
+        ((center - spread) / (1.0 + z_squared / games)).max(0.0)
+    }
+
+    /// Returns how often the move is chosen: popular and successful moves are preferred.
+    fn weight(&self, color: Color) -> f64 {
+        self.games() as f64 * self.confident_score(color)
+    }
+
+    /// Returns how far the expected score can be trusted, which grows with the number of games.
+    fn confidence(&self, color: Color) -> f64 {
+        let expected_score = self.expected_score(color);
+        if expected_score == 0.0 {
+            return 0.0;
+        }
+        self.confident_score(color) / expected_score
+    }
+}
+
 #[derive(Deserialize)]
 struct OpeningResponse {
     pub white: i32,
@@ -28,60 +88,306 @@ struct OpeningResponse {
 
 /// An implementation of MoveProvider that uses the Lichess Opening Explorer
 /// to find the best move in an opening situation.
+///
+/// Moves with fewer games than the ExplorerMinGames option are ignored. The other moves are
+/// weighted by their number of games times the lower bound of their expected score, so the engine
+/// plays popular and successful moves and avoids rare dubious sidelines. The BookVariety option
+/// sets how random the weighted choice is.
+///
+/// Once the explorer knows no more moves, it is not asked again before a new game starts
+/// or a position before the end of the opening is set up.
 #[derive(Debug)]
 pub struct Openings {
-    out_of_opening: bool,
+    /// The move counter of the first position the explorer knew no moves for
+    out_of_opening: Option<u16>,
+    http_client: HttpClient,
 }
 
 impl Openings {
     pub fn new() -> Openings {
-        Openings { out_of_opening: false }
+        Openings {
+            out_of_opening: None,
+            http_client: HttpClient::new("Openings"),
+        }
     }
 }
 
 impl MoveProvider for Openings{
-    fn get_recommended_moves(&mut self, board: &Board, options: Options) -> Vec<ChessMove> {
-        let fen_string: String = Converter::convert_board_to_string(&board);
+    fn get_recommended_moves(&mut self, board: &Board, options: Options) -> Vec<ScoredMove> {
+        let fen_string: String = Converter::convert_board_to_string(board);
 
-        if self.out_of_opening {
+        if self.out_of_opening.is_some() {
             info!("We are out of the opening");
             return Vec::new();
         }
 
         info!("Requesting opening moves for: [{}]", fen_string);
 
-        let body = get("https://explorer.lichess.ovh/masters?fen=".to_string() + fen_string.as_str() + "&topGames=0");
-        match body {
-            Ok(body) => {
-                let response_string: String = match body.text() {
-                    Ok(text) => text,
-                    Err(e) => { warn!("Could not convert response to string: {}", e); return Vec::new(); }
-                };
+        let url = match explorer_url(&options, &fen_string, board.next_color()) {
+            Ok(url) => url,
+            Err(error) => {
+                warn!("The opening explorer can not be asked: {}", error);
+                return Vec::new();
+            }
+        };
+        // the explorer does not look at the move counters
+        let cache_key = cache_key(&url, 4);
+        let cached_response = ResponseCache::shared(&options).get(&cache_key);
+        let is_cached = cached_response.is_some();
+        let response = match cached_response {
+            Some(response) => response,
+            None => match self.http_client.get_text(url.as_str(), options.search_control()) {
+                Ok(response) => response,
+                Err(error) => {
+                    warn!("Request failed: {}", error);
+                    return Vec::new();
+                }
+            },
+        };
 
-                info!("Received answer [{}]", response_string);
+        // the player database streams its results as it searches the games, the last line is complete
+        let last_line = response.lines().rfind(|line| !line.trim().is_empty()).unwrap_or_default();
+        let opening_data: OpeningResponse = match serde_json::from_str(last_line) {
+            Ok(data) => data,
+            Err(error) => {
+                warn!("Could not parse response string: {}", error);
+                return Vec::new();
+            }
+        };
+        if !is_cached {
+            ResponseCache::shared(&options).insert(&cache_key, last_line.to_string());
+        }
 
-                let opening_data: OpeningResponse = match serde_json::from_str(response_string.as_str()) {
-                    Ok(data) => data,
-                    Err(e) => { warn!("Could not parse response string: {}", e); return Vec::new(); }
-                };
+        info!("Received: [{} moves]", opening_data.moves.len());
 
-                info!("Received: [{} moves]", opening_data.moves.len());
+        if opening_data.moves.is_empty() {
+            self.out_of_opening = Some(board.move_counter());
+            info!("There are no more opening moves available");
+        }
 
-                if opening_data.moves.is_empty() {
-                    self.out_of_opening = true;
-                    info!("There are no more opening moves available");
-                }
+        let color = board.next_color();
+        let explorer_moves = frequent_moves(board, &opening_data.moves, options.explorer_min_games());
+        let weighted_moves: Vec<(ChessMove, f64)> = explorer_moves.iter()
+            .map(|(chess_move, mov)| (*chess_move, mov.weight(color)))
+            .collect();
 
-                opening_data.moves.into_iter()
-                    .map(|mov| Converter::convert_string_to_move(&mov.uci))
-                    .map(|mov| Converter::sanitize_move(&board, &mov))
+        match choose_weighted_move(&weighted_moves, options.book_variety()) {
+            Some(chess_move) => {
+                info!("Playing explorer move [{:?}]", chess_move);
+                explorer_moves.iter()
+                    .filter(|(explorer_move, _)| *explorer_move == chess_move)
+                    .map(|(explorer_move, mov)| ScoredMove::new(*explorer_move, mov.expected_score(color), mov.confidence(color), "Openings"))
                     .collect()
-            },
-            Err(error) => {
-                warn!("Request failed: {}", error);
-
+            }
+            None => {
+                info!("No explorer move has enough games");
                 Vec::new()
-            },
+            }
         }
     }
-}
\ No newline at end of file
+
+    fn new_game(&mut self) {
+        self.out_of_opening = None;
+    }
+
+    fn position_changed(&mut self, board: &Board) {
+        // a position before the end of the opening belongs to another game or a take back
+        if self.out_of_opening.is_some_and(|move_counter| board.move_counter() < move_counter) {
+            info!("The position is before the end of the opening, asking the explorer again");
+            self.out_of_opening = None;
+        }
+    }
+}
+
+/// Returns the moves of the explorer that were played in at least the minimum number of games,
+/// the heaviest move first.
+fn frequent_moves<'a>(board: &Board, moves: &'a [OpeningMove], min_games: u32) -> Vec<(ChessMove, &'a OpeningMove)> {
+    let color = board.next_color();
+    let mut explorer_moves: Vec<(ChessMove, &OpeningMove)> = moves.iter()
+        .filter(|mov| mov.games() >= min_games.max(1))
+        .map(|mov| {
+            debug!("Explorer move [{}] with [{}] games, score [{:.3}], confident score [{:.3}], average rating [{:?}]",
+                mov.uci, mov.games(), mov.expected_score(color), mov.confident_score(color), mov.average_rating);
+            (Converter::sanitize_move(board, &Converter::convert_string_to_move(&mov.uci)), mov)
+        })
+        .collect();
+    explorer_moves.sort_by(|(_, first), (_, second)| second.weight(color).total_cmp(&first.weight(color)));
+    explorer_moves
+}
+
+/// Builds the url of the request for the given position from the explorer options.
+/// The player database contains the games the player played with the color to move.
+fn explorer_url(options: &Options, fen: &str, color: Color) -> Result<Url, String> {
+    let database = options.explorer_database();
+    let mut parameters: Vec<(&str, String)> = vec![("fen", fen.to_string())];
+
+    match database {
+        "masters" => {
+            parameters.push(("topGames", options.explorer_top_games().to_string()));
+        }
+        "lichess" => {
+            parameters.push(("variant", "standard".to_string()));
+            parameters.push(("topGames", options.explorer_top_games().to_string()));
+            parameters.push(("recentGames", "0".to_string()));
+            if !options.explorer_ratings().is_empty() {
+                parameters.push(("ratings", options.explorer_ratings().to_string()));
+            }
+        }
+        "player" => {
+            if options.explorer_player().is_empty() {
+                return Err("the player database needs the name of a player".to_string());
+            }
+            parameters.push(("player", options.explorer_player().to_string()));
+            parameters.push(("color", if color == Color::WHITE { "white" } else { "black" }.to_string()));
+            parameters.push(("recentGames", "0".to_string()));
+        }
+        _ => return Err(format!("the database {} is unknown", database)),
+    }
+    // the games of masters are not split by speed
+    if database != "masters" && !options.explorer_speeds().is_empty() {
+        parameters.push(("speeds", options.explorer_speeds().to_string()));
+    }
+
+    let base_url = options.explorer_url().trim_end_matches('/');
+    Url::parse_with_params(&format!("{}/{}", base_url, database), &parameters).map_err(|error| error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datamodel::enums::difficulty::Difficulty;
+    use crate::move_provider::mock_server::{MockResponse, MockServer};
+
+    #[test]
+    fn masters_response_is_parsed() {
+        let response = r#"{"white":1212,"draws":1543,"black":843,"moves":[
+            {"uci":"e2e4","san":"e4","averageRating":2402,"white":512,"draws":640,"black":371,"game":null},
+            {"uci":"d2d4","san":"d4","averageRating":2411,"white":433,"draws":590,"black":302,"game":null}],
+            "topGames":[],"opening":null}"#;
+        let data: OpeningResponse = serde_json::from_str(response).unwrap();
+        assert_eq!(data.moves.len(), 2);
+        assert_eq!(data.moves[0].uci, "e2e4");
+        assert_eq!(data.moves[0].average_rating, Some(2402));
+        assert_eq!(data.moves[1].games(), 1325);
+    }
+
+    #[test]
+    fn player_response_is_parsed() {
+        let response = r#"{"white":10,"draws":2,"black":5,"moves":[
+            {"uci":"e7e5","san":"e5","averageOpponentRating":1850,"performance":1912,"white":6,"draws":1,"black":3,"game":null},
+            {"uci":"c7c5","san":"c5","averageOpponentRating":1790,"performance":1654,"white":4,"draws":1,"black":2,
+             "game":{"id":"abcdefgh","winner":"white","speed":"blitz","mode":"rated","white":{"name":"a","rating":1800},
+                     "black":{"name":"b","rating":1700},"year":2024,"month":"2024-05"}}],
+            "recentGames":[],"opening":{"eco":"B00","name":"King's Pawn"},"queuePosition":0}"#;
+        let data: OpeningResponse = serde_json::from_str(response).unwrap();
+        assert_eq!(data.moves.len(), 2);
+        assert_eq!(data.moves[0].average_rating, Some(1850));
+        assert_eq!(data.moves[1].uci, "c7c5");
+        assert_eq!(data.moves[1].games(), 7);
+    }
+
+    fn opening_move(uci: &str, white: i32, draws: i32, black: i32) -> OpeningMove {
+        OpeningMove { uci: uci.to_string(), average_rating: None, white, draws, black }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-4, "{} is not {}", actual, expected);
+    }
+
+    #[test]
+    fn confident_score_is_the_wilson_lower_bound() {
+        assert_eq!(opening_move("e2e4", 0, 0, 0).confident_score(Color::WHITE), 0.0);
+        assert_eq!(opening_move("e2e4", 0, 0, 0).weight(Color::WHITE), 0.0);
+
+        let all_wins = opening_move("e2e4", 10, 0, 0);
+        assert_close(all_wins.confident_score(Color::WHITE), 0.72247);
+        assert_eq!(all_wins.confident_score(Color::BLACK), 0.0);
+
+        let few_games = opening_move("e2e4", 5, 0, 5);
+        let many_games = opening_move("d2d4", 500, 0, 500);
+        assert_close(few_games.confident_score(Color::WHITE), 0.23659);
+        assert_close(many_games.confident_score(Color::WHITE), 0.46912);
+        // draws count half
+        assert_close(opening_move("c2c4", 0, 10, 0).confident_score(Color::BLACK), 0.23659);
+        assert_close(opening_move("c2c4", 0, 1000, 0).expected_score(Color::WHITE), 0.5);
+    }
+
+    #[test]
+    fn weight_prefers_popular_and_successful_moves() {
+        let popular = opening_move("e2e4", 500, 0, 500);
+        let rare = opening_move("b2b4", 5, 0, 5);
+        let successful = opening_move("d2d4", 600, 0, 400);
+        assert!(popular.weight(Color::WHITE) > 90.0 * rare.weight(Color::WHITE));
+        assert!(successful.weight(Color::WHITE) > popular.weight(Color::WHITE));
+        assert!(successful.weight(Color::BLACK) < popular.weight(Color::BLACK));
+
+        // the confidence grows with the number of games
+        assert!(rare.confidence(Color::WHITE) < 0.5);
+        assert!(popular.confidence(Color::WHITE) > 0.9);
+    }
+
+    #[test]
+    fn moves_with_too_few_games_are_never_chosen() {
+        let board = Converter::convert_string_to_board("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string());
+        let moves = vec![
+            opening_move("b2b4", 9, 0, 0),
+            opening_move("e2e4", 40, 20, 40),
+            opening_move("g2g4", 0, 0, 0),
+            opening_move("d2d4", 30, 20, 10),
+        ];
+
+        let explorer_moves = frequent_moves(&board, &moves, 10);
+        let ucis: Vec<&str> = explorer_moves.iter().map(|(_, mov)| mov.uci.as_str()).collect();
+        assert_eq!(ucis, vec!["e2e4", "d2d4"]);
+
+        let weighted_moves: Vec<(ChessMove, f64)> = explorer_moves.iter()
+            .map(|(chess_move, mov)| (*chess_move, mov.weight(Color::WHITE)))
+            .collect();
+        for _ in 0..200 {
+            let chosen = choose_weighted_move(&weighted_moves, 100).map(|chess_move| Converter::convert_move_to_string(&chess_move));
+            assert!(matches!(chosen.as_deref(), Some("e2e4") | Some("d2d4")), "{:?}", chosen);
+        }
+
+        // a minimum of 0 still needs one game
+        assert_eq!(frequent_moves(&board, &moves, 0).len(), 3);
+    }
+
+    #[test]
+    fn explorer_is_asked_again_before_the_end_of_the_opening_and_in_a_new_game() {
+        let server = MockServer::start(vec![MockResponse::ok(r#"{"white":0,"draws":0,"black":0,"moves":[]}"#)]);
+        let mut options = Options::new(Difficulty::NORMAL, 4);
+        options.set_explorer_url(server.base_url().to_string());
+        // every answer has to come from the server
+        options.set_cache_size(0);
+        let board_at = |move_counter: u16| Converter::convert_string_to_board(
+            format!("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 {}", move_counter));
+        let mut openings = Openings::new();
+
+        assert!(openings.get_recommended_moves(&board_at(12), options.clone()).is_empty());
+        assert_eq!(server.request_times().len(), 1);
+
+        // later positions of the same game are out of the opening as well
+        openings.position_changed(&board_at(13));
+        assert!(openings.get_recommended_moves(&board_at(13), options.clone()).is_empty());
+        openings.position_changed(&board_at(12));
+        assert!(openings.get_recommended_moves(&board_at(12), options.clone()).is_empty());
+        assert_eq!(server.request_times().len(), 1);
+
+        openings.position_changed(&board_at(5));
+        assert!(openings.get_recommended_moves(&board_at(5), options.clone()).is_empty());
+        assert_eq!(server.request_times().len(), 2);
+
+        assert!(openings.get_recommended_moves(&board_at(8), options.clone()).is_empty());
+        assert_eq!(server.request_times().len(), 2);
+        openings.new_game();
+        assert!(openings.get_recommended_moves(&board_at(8), options.clone()).is_empty());
+        assert_eq!(server.request_times().len(), 3);
+    }
+
+    #[test]
+    fn missing_rating_is_accepted() {
+        let response = r#"{"white":0,"draws":0,"black":0,"moves":[{"uci":"g1f3","white":1,"draws":0,"black":0}]}"#;
+        let data: OpeningResponse = serde_json::from_str(response).unwrap();
+        assert_eq!(data.moves[0].average_rating, None);
+    }
+}
@@ -0,0 +1,258 @@
+use std::fs;
+use std::io;
+use log::{debug, info, warn};
+use rand::random_range;
+use crate::converter::converter::Converter;
+use crate::datamodel::board::Board;
+use crate::datamodel::chess_move::ChessMove;
+use crate::datamodel::enums::file::File;
+use crate::datamodel::enums::piece_type::PieceType;
+use crate::datamodel::enums::rank::Rank;
+use crate::datamodel::field::Field;
+use crate::datamodel::options::Options;
+use crate::datamodel::polyglot_zobrist::PolyglotZobrist;
+use crate::move_provider::MoveProvider;
+use crate::move_provider::scored_move::ScoredMove;
+use crate::rules::piece_rule::PieceRule;
+use crate::rules::RulesProvider;
+
+/// Size of an entry of a Polyglot book in bytes
+const ENTRY_SIZE: usize = 16;
+/// Book variety that picks the moves in proportion to their weights
+pub const MAX_BOOK_VARIETY: u32 = 100;
+
+/// A move of a Polyglot book for the position with the given key.
+#[derive(Debug, Clone, Copy)]
+struct BookEntry {
+    key: u64,
+    /// The move as stored by Polyglot, the king captures its own rook when castling
+    raw_move: u16,
+    weight: u16,
+}
+
+/// An implementation of MoveProvider that plays the moves of a local Polyglot opening book.
+///
+/// The book is given by the BookFile option. The BookVariety option sets how random the moves are chosen:
+/// 0 always plays the move with the highest weight, 100 chooses the moves in proportion to their weights.
+#[derive(Debug)]
+pub struct PolyglotBook {
+    /// The book file the entries were loaded from
+    book_file: String,
+    /// The entries of the book sorted by key
+    entries: Vec<BookEntry>,
+}
+
+impl PolyglotBook {
+    pub fn new() -> PolyglotBook {
+        PolyglotBook {
+            book_file: String::new(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Loads the book of the options, if it was changed since the last search.
+    fn configure(&mut self, options: &Options) {
+        if options.book_file() == self.book_file {
+            return;
+        }
+        self.book_file = options.book_file().to_string();
+        self.entries = Vec::new();
+
+        if self.book_file.is_empty() {
+            info!("No opening book configured");
+            return;
+        }
+        match load_entries(&self.book_file) {
+            Ok(entries) => {
+                info!("Loaded [{}] entries from opening book [{}]", entries.len(), self.book_file);
+                self.entries = entries;
+            }
+            Err(error) => {
+                warn!("The opening book [{}] could not be loaded: {}", self.book_file, error);
+                println!("info string the opening book {} could not be loaded: {}", self.book_file, error);
+            }
+        }
+    }
+
+    /// Returns the legal moves of the book for the given position with their weights.
+    fn book_moves(&self, board: &Board) -> Vec<(ChessMove, u16)> {
+        let key = PolyglotZobrist::hash(board);
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let legal_moves = PieceRule::get_legal_moves(board, &board.next_color());
+
+        self.entries[start..].iter()
+            .take_while(|entry| entry.key == key)
+            .map(|entry| (Converter::sanitize_move(board, &decode_move(entry.raw_move)), entry.weight))
+            // different positions may share a key, so only trust legal moves
+            .filter(|(chess_move, _)| legal_moves.contains(chess_move))
+            .collect()
+    }
+}
+
+impl MoveProvider for PolyglotBook {
+    fn get_recommended_moves(&mut self, board: &Board, options: Options) -> Vec<ScoredMove> {
+        self.configure(&options);
+        if self.entries.is_empty() {
+            return Vec::new();
+        }
+
+        let book_moves = self.book_moves(board);
+        debug!("Found book moves [{:?}]", book_moves);
+        let weighted_moves: Vec<(ChessMove, f64)> = book_moves.iter()
+            .map(|(chess_move, weight)| (*chess_move, *weight as f64))
+            .collect();
+        match choose_weighted_move(&weighted_moves, options.book_variety()) {
+            Some(chess_move) => {
+                info!("Playing book move [{:?}]", chess_move);
+                // the book knows how often a move was played, but not how it scored
+                let total_weight: f64 = weighted_moves.iter().map(|(_, weight)| weight).sum();
+                let weight = weighted_moves.iter()
+                    .find(|(book_move, _)| *book_move == chess_move)
+                    .map_or(0.0, |(_, weight)| *weight);
+                vec![ScoredMove::new(chess_move, 0.5, weight / total_weight, "PolyglotBook")]
+            }
+            None => {
+                info!("The position is not in the opening book");
+                Vec::new()
+            }
+        }
+    }
+
+    fn get_ponder_move(&self, board: &Board, chess_move: &ChessMove) -> Option<ChessMove> {
+        let mut new_board = board.clone();
+        new_board.play_move(chess_move);
+
+        // the opponent most likely answers with the main line of the book
+        self.book_moves(&new_board).into_iter()
+            .max_by_key(|(_, weight)| *weight)
+            .map(|(chess_move, _)| chess_move)
+    }
+}
+
+/// Reads all entries of a Polyglot book. Each entry consists of the key, the move,
+/// the weight and the learn value, all of them big endian.
+fn load_entries(path: &str) -> io::Result<Vec<BookEntry>> {
+    let bytes = fs::read(path)?;
+    if bytes.len() % ENTRY_SIZE != 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "the size is not a multiple of the entry size"));
+    }
+
+    Ok(bytes.chunks_exact(ENTRY_SIZE)
+        .map(|entry| BookEntry {
+            key: u64::from_be_bytes(entry[0..8].try_into().unwrap()),
+            raw_move: u16::from_be_bytes(entry[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(entry[10..12].try_into().unwrap()),
+        })
+        .collect())
+}
+
+/// Decodes a move of a Polyglot book. From the lowest bits on, it holds the file and rank
+/// of the target field, the file and rank of the starting field and the promotion piece with 3 bits each.
+fn decode_move(raw_move: u16) -> ChessMove {
+    let bits = |offset: u16| ((raw_move >> offset) & 7) as usize;
+    let promote_to = match bits(12) {
+        1 => Some(PieceType::KNIGHT),
+        2 => Some(PieceType::BISHOP),
+        3 => Some(PieceType::ROOK),
+        4 => Some(PieceType::QUEEN),
+        _ => None,
+    };
+
+    ChessMove::new(
+        Field::new(File::from_index(bits(6)), Rank::from_index(bits(9))),
+        Field::new(File::from_index(bits(0)), Rank::from_index(bits(3))),
+        promote_to,
+    )
+}
+
+/// Chooses one of the weighted moves. The lower the variety, the more the choice prefers heavy moves:
+/// 0 always chooses the heaviest move, MAX_BOOK_VARIETY chooses the moves in proportion to their weights.
+pub fn choose_weighted_move(weighted_moves: &[(ChessMove, f64)], variety: u32) -> Option<ChessMove> {
+    // moves without weight are in the book to be avoided
+    let candidates: Vec<&(ChessMove, f64)> = weighted_moves.iter().filter(|(_, weight)| *weight > 0.0).collect();
+    let heaviest = candidates.iter().max_by(|(_, first), (_, second)| first.total_cmp(second))?;
+    if variety == 0 {
+        return Some(heaviest.0);
+    }
+
+    let exponent = MAX_BOOK_VARIETY as f64 / variety.min(MAX_BOOK_VARIETY) as f64;
+    let heaviest_weight = heaviest.1;
+    // relative weights keep the powers small
+    let shares: Vec<f64> = candidates.iter()
+        .map(|(_, weight)| (weight / heaviest_weight).powf(exponent))
+        .collect();
+
+    let mut remaining = random_range(0.0..shares.iter().sum::<f64>());
+    for (candidate, share) in candidates.iter().zip(&shares) {
+        if remaining < *share {
+            return Some(candidate.0);
+        }
+        remaining -= share;
+    }
+    Some(heaviest.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_move(from: &str, to: &str, promotion: u16) -> u16 {
+        let square = |name: &str| {
+            let bytes = name.as_bytes();
+            (bytes[0] - b'a') as u16 | (((bytes[1] - b'1') as u16) << 3)
+        };
+        square(to) | (square(from) << 6) | (promotion << 12)
+    }
+
+    fn chess_move(uci: &str) -> ChessMove {
+        Converter::convert_string_to_move(&uci.to_string())
+    }
+
+    #[test]
+    fn moves_are_decoded() {
+        assert_eq!(decode_move(raw_move("e2", "e4", 0)), chess_move("e2e4"));
+        assert_eq!(decode_move(raw_move("g8", "f6", 0)), chess_move("g8f6"));
+        assert_eq!(decode_move(raw_move("a7", "a8", 1)), chess_move("a7a8n"));
+        assert_eq!(decode_move(raw_move("b7", "b8", 2)), chess_move("b7b8b"));
+        assert_eq!(decode_move(raw_move("c2", "c1", 3)), chess_move("c2c1r"));
+        assert_eq!(decode_move(raw_move("h7", "g8", 4)), chess_move("h7g8q"));
+    }
+
+    #[test]
+    fn castling_moves_are_sanitized() {
+        let board = Converter::convert_string_to_board("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 0 1".to_string());
+        let book = PolyglotBook {
+            book_file: String::new(),
+            entries: vec![
+                BookEntry { key: PolyglotZobrist::hash(&board), raw_move: raw_move("e1", "h1", 0), weight: 2 },
+                BookEntry { key: PolyglotZobrist::hash(&board), raw_move: raw_move("e1", "a1", 0), weight: 1 },
+            ],
+        };
+        assert_eq!(book.book_moves(&board), vec![(chess_move("e1g1"), 2), (chess_move("e1c1"), 1)]);
+    }
+
+    #[test]
+    fn only_legal_moves_of_the_position_are_returned() {
+        let board = Converter::convert_string_to_board("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string());
+        let key = PolyglotZobrist::hash(&board);
+        let mut entries = vec![
+            BookEntry { key: key.wrapping_sub(1), raw_move: raw_move("d2", "d4", 0), weight: 9 },
+            BookEntry { key, raw_move: raw_move("e2", "e4", 0), weight: 5 },
+            BookEntry { key, raw_move: raw_move("e2", "e5", 0), weight: 7 },
+            BookEntry { key: key.wrapping_add(1), raw_move: raw_move("c2", "c4", 0), weight: 9 },
+        ];
+        entries.sort_by_key(|entry| entry.key);
+        let book = PolyglotBook { book_file: String::new(), entries };
+        assert_eq!(book.book_moves(&board), vec![(chess_move("e2e4"), 5)]);
+    }
+
+    #[test]
+    fn variety_zero_chooses_the_heaviest_move() {
+        let weighted_moves = [(chess_move("e2e4"), 3.0), (chess_move("d2d4"), 8.0), (chess_move("c2c4"), 0.0)];
+        assert_eq!(choose_weighted_move(&weighted_moves, 0), Some(chess_move("d2d4")));
+        assert_eq!(choose_weighted_move(&[(chess_move("c2c4"), 0.0)], MAX_BOOK_VARIETY), None);
+        for _ in 0..20 {
+            assert_ne!(choose_weighted_move(&weighted_moves, MAX_BOOK_VARIETY), Some(chess_move("c2c4")));
+        }
+    }
+}
@@ -0,0 +1,417 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::sync::{LazyLock, Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+use log::{debug, info, warn};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use crate::datamodel::options::Options;
+
+/// Maximum number of entries of the CacheSize option
+pub const MAX_CACHE_SIZE: u32 = 10_000_000;
+/// Maximum time of the CacheTtl option in hours, about ten years
+pub const MAX_CACHE_TTL: u32 = 87_600;
+/// The file is rewritten once it holds this many times more lines than the cache has entries
+const COMPACTION_FACTOR: usize = 2;
+
+static RESPONSE_CACHE: LazyLock<Mutex<ResponseCache>> = LazyLock::new(|| Mutex::new(ResponseCache::new()));
+
+/// A cached answer of a web service.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    key: String,
+    response: String,
+    /// Seconds since the unix epoch when the answer was received
+    stored_at: u64,
+}
+
+/// Keeps the answers of the opening explorer and the tablebase, so positions that were
+/// asked for before do not need the network.
+///
+/// The entries are kept in memory and, if the CacheFile option is set, appended to the file
+/// as json lines, so the cache survives a restart. Entries older than the CacheTtl option
+/// are discarded, and the oldest entries are discarded once the cache holds more than
+/// CacheSize entries.
+#[derive(Debug)]
+pub struct ResponseCache {
+    cache_file: String,
+    /// Time to live of the entries in seconds
+    ttl: u64,
+    max_entries: usize,
+    entries: HashMap<String, CacheEntry>,
+    /// Number of lines of the cache file
+    file_lines: usize,
+}
+
+impl ResponseCache {
+    fn new() -> ResponseCache {
+        ResponseCache {
+            cache_file: String::new(),
+            ttl: 0,
+            max_entries: 0,
+            entries: HashMap::new(),
+            file_lines: 0,
+        }
+    }
+
+    /// Returns the cache shared by all move providers, configured by the options.
+    pub fn shared(options: &Options) -> MutexGuard<'static, ResponseCache> {
+        let mut cache = RESPONSE_CACHE.lock().unwrap();
+        cache.configure(options);
+        cache
+    }
+
+    /// Applies the cache options, if they changed since the last call. The file is loaded
+    /// when it was changed. Unchanged options cost nothing, as every request passes them.
+    fn configure(&mut self, options: &Options) {
+        let ttl = options.cache_ttl() as u64 * 3600;
+        let max_entries = options.cache_size() as usize;
+        if ttl == self.ttl && max_entries == self.max_entries && options.cache_file() == self.cache_file {
+            return;
+        }
+        self.ttl = ttl;
+        self.max_entries = max_entries;
+
+        if options.cache_file() != self.cache_file {
+            self.cache_file = options.cache_file().to_string();
+            self.entries.clear();
+            self.file_lines = 0;
+
+            if !self.cache_file.is_empty() {
+                match self.load() {
+                    Ok(()) => info!("Loaded [{}] cached responses from [{}]", self.entries.len(), self.cache_file),
+                    Err(error) => {
+                        warn!("The response cache [{}] could not be loaded: {}", self.cache_file, error);
+                        println!("info string the response cache {} could not be loaded: {}", self.cache_file, error);
+                    }
+                }
+            }
+        }
+
+        self.discard_expired();
+        self.shrink_to_limit();
+        self.compact_if_needed();
+    }
+
+    /// Returns the cached response for the key, if it did not expire. An expired entry is discarded.
+    pub fn get(&mut self, key: &str) -> Option<String> {
+        let now = now();
+        match self.entries.get(key) {
+            Some(entry) if now.saturating_sub(entry.stored_at) <= self.ttl => {
+                debug!("Response cache hit for [{}]", key);
+                Some(entry.response.clone())
+            }
+            Some(_) => {
+                self.entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Stores the response for the key and appends it to the cache file.
+    pub fn insert(&mut self, key: &str, response: String) {
+        if self.max_entries == 0 {
+            return;
+        }
+        let entry = CacheEntry {
+            key: key.to_string(),
+            response,
+            stored_at: now(),
+        };
+
+        if !self.cache_file.is_empty() {
+            if let Err(error) = self.append(&entry) {
+                warn!("The response could not be written to the cache [{}]: {}", self.cache_file, error);
+            }
+        }
+        self.entries.insert(entry.key.clone(), entry);
+        self.shrink_to_limit();
+        self.compact_if_needed();
+    }
+
+    /// Reads the entries of the cache file. Later lines replace earlier lines of the same key.
+    fn load(&mut self) -> io::Result<()> {
+        let file = match File::open(&self.cache_file) {
+            Ok(file) => file,
+            // the cache file is created with the first response
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(error) => return Err(error),
+        };
+
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            self.file_lines += 1;
+            match serde_json::from_str::<CacheEntry>(&line) {
+                Ok(entry) => {
+                    self.entries.insert(entry.key.clone(), entry);
+                }
+                Err(error) => debug!("Skipping invalid line of the response cache: {}", error),
+            }
+        }
+        Ok(())
+    }
+
+    fn append(&mut self, entry: &CacheEntry) -> io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.cache_file)?;
+        writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        self.file_lines += 1;
+        Ok(())
+    }
+
+    fn discard_expired(&mut self) {
+        let now = now();
+        let ttl = self.ttl;
+        self.entries.retain(|_, entry| now.saturating_sub(entry.stored_at) <= ttl);
+    }
+
+    /// Discards the expired and then the oldest entries, if the cache holds more than
+    /// the maximum number of entries.
+    fn shrink_to_limit(&mut self) {
+        if self.entries.len() <= self.max_entries {
+            return;
+        }
+        self.discard_expired();
+        if self.entries.len() <= self.max_entries {
+            return;
+        }
+        let mut ages: Vec<(u64, String)> = self.entries.values()
+            .map(|entry| (entry.stored_at, entry.key.clone()))
+            .collect();
+        ages.sort_unstable();
+        let excess = self.entries.len() - self.max_entries;
+        for (_, key) in ages.into_iter().take(excess) {
+            self.entries.remove(&key);
+        }
+    }
+
+    /// Rewrites the cache file with the current entries, once it mostly holds replaced,
+    /// expired or discarded lines.
+    fn compact_if_needed(&mut self) {
+        if self.cache_file.is_empty() || self.file_lines <= COMPACTION_FACTOR * self.entries.len().max(1) {
+            return;
+        }
+        match self.rewrite() {
+            Ok(()) => debug!("Compacted the response cache [{}] to [{}] lines", self.cache_file, self.file_lines),
+            Err(error) => warn!("The response cache [{}] could not be compacted: {}", self.cache_file, error),
+        }
+    }
+
+    fn rewrite(&mut self) -> io::Result<()> {
+        let temporary_file = format!("{}.tmp", self.cache_file);
+        let mut entries: Vec<&CacheEntry> = self.entries.values().collect();
+        entries.sort_by_key(|entry| entry.stored_at);
+
+        let mut writer = BufWriter::new(File::create(&temporary_file)?);
+        for entry in &entries {
+            writeln!(writer, "{}", serde_json::to_string(entry)?)?;
+        }
+        writer.flush()?;
+        drop(writer);
+
+        fs::rename(&temporary_file, &self.cache_file)?;
+        self.file_lines = entries.len();
+        Ok(())
+    }
+}
+
+/// Returns the key of a request, in which the fen only keeps its first fields.
+/// The move counters do not change most answers, so positions reached by different
+/// move orders share their entry.
+pub fn cache_key(url: &Url, fen_fields: usize) -> String {
+    let mut key = url.clone();
+    let parameters: Vec<(String, String)> = url.query_pairs()
+        .map(|(name, value)| {
+            let value = if name == "fen" {
+                value.split_whitespace().take(fen_fields).collect::<Vec<&str>>().join(" ")
+            } else {
+                value.into_owned()
+            };
+            (name.into_owned(), value)
+        })
+        .collect();
+    key.query_pairs_mut().clear().extend_pairs(parameters);
+    key.to_string()
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datamodel::enums::difficulty::Difficulty;
+
+    /// Returns the path of a cache file in the temporary directory, which does not exist yet.
+    fn temporary_cache_file(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("flengine-cache-{}-{}.jsonl", std::process::id(), name));
+        let _ = fs::remove_file(&path);
+        path.to_string_lossy().into_owned()
+    }
+
+    fn entry(key: &str, response: &str, stored_at: u64) -> CacheEntry {
+        CacheEntry { key: key.to_string(), response: response.to_string(), stored_at }
+    }
+
+    fn cache_with(ttl: u64, max_entries: usize) -> ResponseCache {
+        ResponseCache { ttl, max_entries, ..ResponseCache::new() }
+    }
+
+    fn line(entry: &CacheEntry) -> String {
+        serde_json::to_string(entry).unwrap()
+    }
+
+    #[test]
+    fn expired_entries_are_not_returned() {
+        let mut cache = cache_with(3600, 10);
+        cache.entries.insert("old".to_string(), entry("old", "stale", now() - 3601));
+        cache.entries.insert("new".to_string(), entry("new", "fresh", now() - 3599));
+
+        assert_eq!(cache.get("old"), None);
+        assert!(!cache.entries.contains_key("old"));
+        assert_eq!(cache.get("new"), Some("fresh".to_string()));
+        assert_eq!(cache.get("missing"), None);
+    }
+
+    #[test]
+    fn inserted_responses_are_returned() {
+        let mut cache = cache_with(3600, 10);
+        cache.insert("key", "answer".to_string());
+        assert_eq!(cache.get("key"), Some("answer".to_string()));
+
+        // a cache without entries stores nothing
+        let mut cache = cache_with(3600, 0);
+        cache.insert("key", "answer".to_string());
+        assert_eq!(cache.get("key"), None);
+    }
+
+    #[test]
+    fn oldest_entries_are_dropped_over_the_limit() {
+        let mut cache = cache_with(3600, 3);
+        let now = now();
+        for (index, key) in ["a", "b", "c", "d", "e"].iter().enumerate() {
+            cache.entries.insert(key.to_string(), entry(key, key, now - 100 + index as u64));
+        }
+        cache.shrink_to_limit();
+
+        let mut keys: Vec<&String> = cache.entries.keys().collect();
+        keys.sort();
+        assert_eq!(keys, vec!["c", "d", "e"]);
+    }
+
+    #[test]
+    fn expired_entries_are_dropped_first_over_the_limit() {
+        let mut cache = cache_with(3600, 2);
+        let now = now();
+        cache.entries.insert("expired".to_string(), entry("expired", "", now - 7200));
+        cache.entries.insert("older".to_string(), entry("older", "", now - 60));
+        cache.entries.insert("newer".to_string(), entry("newer", "", now - 30));
+        cache.shrink_to_limit();
+
+        assert_eq!(cache.entries.len(), 2);
+        assert!(cache.entries.contains_key("older") && cache.entries.contains_key("newer"));
+    }
+
+    #[test]
+    fn load_keeps_the_last_line_of_a_key_and_skips_corrupt_lines() {
+        let cache_file = temporary_cache_file("load");
+        let lines = [
+            line(&entry("a", "first", 10)),
+            "{\"key\":\"broken\"".to_string(),
+            line(&entry("b", "other", 11)),
+            "not json at all".to_string(),
+            line(&entry("a", "second", 12)),
+        ];
+        fs::write(&cache_file, lines.join("\n") + "\n").unwrap();
+
+        let mut cache = ResponseCache { cache_file: cache_file.clone(), ..cache_with(3600, 10) };
+        cache.load().unwrap();
+        fs::remove_file(&cache_file).unwrap();
+
+        assert_eq!(cache.entries.len(), 2);
+        assert_eq!(cache.entries["a"].response, "second");
+        assert_eq!(cache.entries["b"].response, "other");
+        assert_eq!(cache.file_lines, 5);
+    }
+
+    #[test]
+    fn missing_file_is_an_empty_cache() {
+        let mut cache = ResponseCache { cache_file: temporary_cache_file("missing"), ..cache_with(3600, 10) };
+        cache.load().unwrap();
+        assert!(cache.entries.is_empty());
+    }
+
+    #[test]
+    fn compaction_rewrites_the_file_with_the_current_entries() {
+        let cache_file = temporary_cache_file("compact");
+        let now = now();
+        let lines: Vec<String> = (0..5).map(|index| line(&entry("a", &index.to_string(), now - 10 + index))).collect();
+        fs::write(&cache_file, lines.join("\n") + "\n").unwrap();
+
+        let mut cache = ResponseCache { cache_file: cache_file.clone(), ..cache_with(3600, 10) };
+        cache.load().unwrap();
+        cache.compact_if_needed();
+        let content = fs::read_to_string(&cache_file).unwrap();
+        fs::remove_file(&cache_file).unwrap();
+
+        assert_eq!(cache.file_lines, 1);
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(serde_json::from_str::<CacheEntry>(lines[0]).unwrap().response, "4");
+    }
+
+    #[test]
+    fn small_files_are_not_compacted() {
+        let cache_file = temporary_cache_file("small");
+        let mut cache = ResponseCache { cache_file: cache_file.clone(), ..cache_with(3600, 10) };
+        cache.insert("a", "first".to_string());
+        cache.insert("a", "second".to_string());
+        let content = fs::read_to_string(&cache_file).unwrap();
+        fs::remove_file(&cache_file).unwrap();
+
+        assert_eq!(content.lines().count(), 2);
+        assert_eq!(cache.file_lines, 2);
+    }
+
+    #[test]
+    fn configure_loads_the_file_only_when_the_options_change() {
+        let cache_file = temporary_cache_file("configure");
+        fs::write(&cache_file, line(&entry("a", "cached", now())) + "\n").unwrap();
+        let mut options = Options::new(Difficulty::NORMAL, 4);
+        options.set_cache_file(cache_file.clone());
+
+        let mut cache = ResponseCache::new();
+        cache.configure(&options);
+        assert_eq!(cache.get("a"), Some("cached".to_string()));
+
+        // entries that are only in memory survive unchanged options
+        cache.entries.insert("b".to_string(), entry("b", "memory", now()));
+        cache.configure(&options);
+        assert!(cache.entries.contains_key("b"));
+
+        options.set_cache_size(1);
+        cache.configure(&options);
+        assert_eq!(cache.entries.len(), 1);
+        fs::remove_file(&cache_file).unwrap();
+    }
+
+    #[test]
+    fn cache_key_drops_the_move_counters() {
+        let url = Url::parse_with_params("https://explorer.lichess.ovh/lichess",
+            &[("fen", "4k3/8/8/8/8/8/4P3/4K3 w - - 12 57"), ("speeds", "blitz,rapid")]).unwrap();
+        let same_position = Url::parse_with_params("https://explorer.lichess.ovh/lichess",
+            &[("fen", "4k3/8/8/8/8/8/4P3/4K3 w - - 0 3"), ("speeds", "blitz,rapid")]).unwrap();
+
+        let key = cache_key(&url, 4);
+        assert_eq!(key, cache_key(&same_position, 4));
+        let key = Url::parse(&key).unwrap();
+        let parameters: Vec<(String, String)> = key.query_pairs().map(|(name, value)| (name.into_owned(), value.into_owned())).collect();
+        assert_eq!(parameters, vec![
+            ("fen".to_string(), "4k3/8/8/8/8/8/4P3/4K3 w - -".to_string()),
+            ("speeds".to_string(), "blitz,rapid".to_string()),
+        ]);
+        assert_ne!(cache_key(&url, 5), cache_key(&same_position, 5));
+    }
+}
@@ -0,0 +1,44 @@
+use crate::datamodel::chess_move::ChessMove;
+
+/// Centipawns that make the player to move about ten times as likely to win as to lose
+const CENTIPAWNS_PER_DECADE: f64 = 400.0;
+
+/// A move recommended by a move provider together with how good the provider thinks it is.
+///
+/// The scores of all move providers share one scale, so the controller can compare a book move
+/// with a tablebase result or a searched move.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoredMove {
+    pub chess_move: ChessMove,
+    /// The expected result for the player who makes the move,
+    /// from 0 for a loss over 0.5 for a draw to 1 for a win
+    pub score: f64,
+    /// How sure the move provider is about the score, from 0 for a guess to 1 for a proven result
+    pub confidence: f64,
+    /// The name of the move provider that recommended the move
+    pub source: &'static str,
+    /// The expected moves of both players after the move, empty if unknown
+    pub principal_variation: Vec<ChessMove>,
+}
+
+impl ScoredMove {
+    pub fn new(chess_move: ChessMove, score: f64, confidence: f64, source: &'static str) -> ScoredMove {
+        ScoredMove {
+            chess_move,
+            score: score.clamp(0.0, 1.0),
+            confidence: confidence.clamp(0.0, 1.0),
+            source,
+            principal_variation: Vec::new(),
+        }
+    }
+
+    pub fn with_principal_variation(mut self, principal_variation: Vec<ChessMove>) -> ScoredMove {
+        self.principal_variation = principal_variation;
+        self
+    }
+
+    /// Converts a rating in centipawns from the view of a player into his expected result.
+    pub fn score_of_centipawns(centipawns: i32) -> f64 {
+        1.0 / (1.0 + 10f64.powf(-centipawns as f64 / CENTIPAWNS_PER_DECADE))
+    }
+}
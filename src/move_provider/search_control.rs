@@ -0,0 +1,204 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use log::info;
+use tokio::select;
+use tokio_util::sync::CancellationToken;
+use crate::datamodel::enums::color::Color;
+use crate::datamodel::search_limits::SearchLimits;
+
+/// Time in milliseconds that is kept back for the communication with the gui
+const MOVE_OVERHEAD: u64 = 50;
+
+/// Number of moves the remaining time is split into, if the gui does not send movestogo
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+#[derive(Debug, Default)]
+struct TimeState {
+    /// Time that may be spent on the move once the search is no longer pondering
+    budget: Option<Duration>,
+    /// Point in time at which the search has to stop. None if the search is not timed (yet).
+    deadline: Option<Instant>,
+    pondering: bool,
+}
+
+/// Controls a running search from the outside.
+///
+/// The search polls `should_stop` to find out whether the gui sent stop or the time is up.
+/// A ponder search is not timed until the gui sends ponderhit, then it turns into a normal
+/// timed search.
+#[derive(Clone, Debug, Default)]
+pub struct SearchControl {
+    limits: SearchLimits,
+    cancellation_token: CancellationToken,
+    ponderhit_token: CancellationToken,
+    time_state: Arc<Mutex<TimeState>>,
+}
+
+impl SearchControl {
+    pub fn new(limits: SearchLimits, cancellation_token: CancellationToken) -> Self {
+        SearchControl {
+            limits,
+            cancellation_token,
+            ponderhit_token: CancellationToken::new(),
+            time_state: Arc::new(Mutex::new(TimeState::default())),
+        }
+    }
+
+    pub fn limits(&self) -> &SearchLimits {
+        &self.limits
+    }
+
+    /// Starts the clock for the player of the given color.
+    /// A ponder search only computes its budget and starts the clock on ponderhit.
+    /// The search thread may start after the gui already sent ponderhit, then the clock starts right away.
+    pub fn start(&self, color: Color) {
+        let mut time_state = self.time_state.lock().unwrap();
+        time_state.budget = compute_time_budget(&self.limits, color);
+        time_state.pondering = self.limits.ponder() && !self.ponderhit_token.is_cancelled();
+
+        if !time_state.pondering {
+            time_state.deadline = time_state.budget.map(|budget| Instant::now() + budget);
+        }
+        info!("Search started with time budget [{:?}], pondering [{}]", time_state.budget, time_state.pondering);
+    }
+
+    /// The opponent played the expected move, so the ponder search becomes a timed search.
+    pub fn ponderhit(&self) {
+        let mut time_state = self.time_state.lock().unwrap();
+        time_state.pondering = false;
+        time_state.deadline = time_state.budget.map(|budget| Instant::now() + budget);
+        self.ponderhit_token.cancel();
+        info!("Ponderhit, search continues with time budget [{:?}]", time_state.budget);
+    }
+
+    pub fn stop(&self) {
+        self.cancellation_token.cancel();
+    }
+
+    pub fn is_pondering(&self) -> bool {
+        self.time_state.lock().unwrap().pondering
+    }
+
+    /// Returns whether the search has to be stopped, because the gui sent stop or the time is up.
+    pub fn should_stop(&self) -> bool {
+        self.cancellation_token.is_cancelled()
+            || self.time_state.lock().unwrap().deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Returns the time until the search has to stop, None if the search is not timed.
+    pub fn time_left(&self) -> Option<Duration> {
+        self.time_state.lock().unwrap().deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Waits until the gui sends stop.
+    pub async fn stopped(&self) {
+        self.cancellation_token.cancelled().await;
+    }
+
+    /// Waits until the result of the search may be sent to the gui.
+    /// An infinite search waits for stop, a ponder search waits for ponderhit or stop.
+    pub async fn wait_until_released(&self) {
+        if self.limits.infinite() {
+            self.cancellation_token.cancelled().await;
+        } else if self.is_pondering() {
+            select! {
+                _ = self.cancellation_token.cancelled() => {},
+                _ = self.ponderhit_token.cancelled() => {},
+            }
+        }
+    }
+}
+
+/// Computes the time that may be spent on the next move for the player of the given color.
+/// Returns None if the search is not limited by time.
+fn compute_time_budget(limits: &SearchLimits, color: Color) -> Option<Duration> {
+    if limits.infinite() {
+        return None;
+    }
+
+    if let Some(move_time) = limits.move_time() {
+        return Some(Duration::from_millis(move_time.saturating_sub(MOVE_OVERHEAD).max(1)));
+    }
+
+    let (time, increment) = match color {
+        Color::WHITE => (limits.white_time(), limits.white_increment()),
+        Color::BLACK => (limits.black_time(), limits.black_increment()),
+    };
+
+    time.map(|time| {
+        // split the remaining time evenly over the remaining moves and use most of the increment
+        let moves_to_go = limits.moves_to_go().unwrap_or(DEFAULT_MOVES_TO_GO).max(1) as u64;
+        let budget = time / moves_to_go + increment.unwrap_or(0) * 3 / 4;
+        Duration::from_millis(budget.min(time.saturating_sub(MOVE_OVERHEAD)).max(1))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits_with_clock(white_time: u64, black_time: u64) -> SearchLimits {
+        let mut limits = SearchLimits::new();
+        limits.set_white_time(Some(white_time));
+        limits.set_black_time(Some(black_time));
+        limits
+    }
+
+    #[test]
+    fn infinite_search_has_no_budget() {
+        let mut limits = limits_with_clock(60_000, 60_000);
+        limits.set_infinite(true);
+        assert_eq!(compute_time_budget(&limits, Color::WHITE), None);
+    }
+
+    #[test]
+    fn search_without_clock_has_no_budget() {
+        let mut limits = SearchLimits::new();
+        limits.set_depth(Some(5));
+        assert_eq!(compute_time_budget(&limits, Color::WHITE), None);
+    }
+
+    #[test]
+    fn move_time_keeps_the_overhead() {
+        let mut limits = limits_with_clock(60_000, 60_000);
+        limits.set_move_time(Some(1_000));
+        assert_eq!(compute_time_budget(&limits, Color::WHITE), Some(Duration::from_millis(950)));
+
+        limits.set_move_time(Some(10));
+        assert_eq!(compute_time_budget(&limits, Color::WHITE), Some(Duration::from_millis(1)));
+    }
+
+    #[test]
+    fn clock_is_split_over_the_remaining_moves() {
+        let limits = limits_with_clock(60_000, 30_000);
+        assert_eq!(compute_time_budget(&limits, Color::WHITE), Some(Duration::from_millis(2_000)));
+        assert_eq!(compute_time_budget(&limits, Color::BLACK), Some(Duration::from_millis(1_000)));
+
+        let mut limits = limits_with_clock(60_000, 30_000);
+        limits.set_moves_to_go(Some(10));
+        assert_eq!(compute_time_budget(&limits, Color::WHITE), Some(Duration::from_millis(6_000)));
+
+        limits.set_moves_to_go(Some(0));
+        assert_eq!(compute_time_budget(&limits, Color::WHITE), Some(Duration::from_millis(59_950)));
+    }
+
+    #[test]
+    fn most_of_the_increment_is_used() {
+        let mut limits = limits_with_clock(60_000, 60_000);
+        limits.set_white_increment(Some(1_000));
+        limits.set_black_increment(Some(2_000));
+        assert_eq!(compute_time_budget(&limits, Color::WHITE), Some(Duration::from_millis(2_750)));
+        assert_eq!(compute_time_budget(&limits, Color::BLACK), Some(Duration::from_millis(3_500)));
+    }
+
+    #[test]
+    fn budget_never_exceeds_the_clock() {
+        let mut limits = limits_with_clock(500, 30);
+        limits.set_white_increment(Some(5_000));
+        limits.set_black_increment(Some(5_000));
+        assert_eq!(compute_time_budget(&limits, Color::WHITE), Some(Duration::from_millis(450)));
+        assert_eq!(compute_time_budget(&limits, Color::BLACK), Some(Duration::from_millis(1)));
+    }
+}
@@ -0,0 +1,68 @@
+use log::info;
+use crate::datamodel::board::Board;
+use crate::datamodel::chess_move::ChessMove;
+use crate::datamodel::options::Options;
+use shakmaty_syzygy::Wdl;
+use crate::move_provider::scored_move::ScoredMove;
+use crate::move_provider::syzygy_tablebases::SyzygyTablebases;
+use crate::move_provider::MoveProvider;
+
+/// An implementation of MoveProvider that plays perfect endgames with local Syzygy tablebases.
+///
+/// The tablebases are given by the SyzygyPath option, so endgames are played without network access.
+/// The moves are ranked by their result, then by the distance to zeroing.
+#[derive(Debug)]
+pub struct SyzygyEndgame {
+    tablebases: SyzygyTablebases,
+    /// The moves of the last position, best move first
+    last_moves: Vec<ChessMove>,
+}
+
+impl SyzygyEndgame {
+    pub fn new() -> SyzygyEndgame {
+        SyzygyEndgame {
+            tablebases: SyzygyTablebases::new(),
+            last_moves: Vec::new(),
+        }
+    }
+}
+
+impl MoveProvider for SyzygyEndgame {
+    fn get_recommended_moves(&mut self, board: &Board, options: Options) -> Vec<ScoredMove> {
+        self.tablebases.configure(&options);
+        self.last_moves = Vec::new();
+
+        let ranked_moves = match self.tablebases.rank_moves(board) {
+            Some(ranked_moves) => ranked_moves,
+            None => return Vec::new(),
+        };
+        info!("Syzygy tablebases ranked [{}] moves: {:?}", ranked_moves.len(), ranked_moves);
+
+        self.last_moves = ranked_moves.iter().map(|ranked_move| ranked_move.chess_move).collect();
+        ranked_moves.into_iter()
+            .map(|ranked_move| ScoredMove::new(ranked_move.chess_move, score_of_wdl(ranked_move.wdl), 1.0, "SyzygyEndgame"))
+            .collect()
+    }
+
+    fn get_ponder_move(&self, board: &Board, chess_move: &ChessMove) -> Option<ChessMove> {
+        if !self.last_moves.contains(chess_move) {
+            return None;
+        }
+        let mut new_board = board.clone();
+        new_board.play_move(chess_move);
+        self.tablebases.rank_moves(&new_board)
+            .and_then(|ranked_moves| ranked_moves.first().map(|ranked_move| ranked_move.chess_move))
+    }
+}
+
+/// Returns the expected result of a tablebase result. The 50-move rule turns a cursed win and
+/// a blessed loss into draws, but they keep the chance that the opponent errs.
+fn score_of_wdl(wdl: Wdl) -> f64 {
+    match wdl {
+        Wdl::Win => 1.0,
+        Wdl::CursedWin => 0.55,
+        Wdl::Draw => 0.5,
+        Wdl::BlessedLoss => 0.45,
+        Wdl::Loss => 0.0,
+    }
+}
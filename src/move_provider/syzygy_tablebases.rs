@@ -0,0 +1,298 @@
+use std::cmp::Reverse;
+use std::env;
+use std::fmt;
+use std::num::NonZeroU32;
+use log::{debug, info, warn};
+use shakmaty::{Bitboard, CastlingMode, Chess, FromSetup, Position, Role, Setup, Square};
+use shakmaty_syzygy::{Tablebase, Wdl};
+use crate::datamodel::board::Board;
+use crate::datamodel::chess_move::ChessMove;
+use crate::datamodel::enums::color::Color;
+use crate::datamodel::enums::file::File;
+use crate::datamodel::enums::piece_type::PieceType;
+use crate::datamodel::enums::rank::Rank;
+use crate::datamodel::field::Field;
+use crate::datamodel::options::Options;
+use crate::rules::piece_rule::PieceRule;
+use crate::rules::RulesProvider;
+
+/// A legal move with the tablebase result of the position it leads to.
+#[derive(Debug, Clone, Copy)]
+pub struct TablebaseMove {
+    pub chess_move: ChessMove,
+    /// The result for the player who makes the move
+    pub wdl: Wdl,
+    /// The distance to zeroing of the reached position from the view of the opponent
+    pub dtz: i32,
+    /// Whether the move is a capture or a pawn move, which resets the 50-move rule
+    pub zeroing: bool,
+    pub checkmate: bool,
+}
+
+/// The local Syzygy tablebases of the SyzygyPath option.
+///
+/// The path may list several directories, separated by ':' on unix and by ';' on windows.
+/// The search probes its positions as if the last move was zeroing, the ranking of the moves
+/// at the root respects the halfmove clock of the board.
+pub struct SyzygyTablebases {
+    /// The path the tablebases were loaded from
+    syzygy_path: String,
+    tablebase: Option<Tablebase<Chess>>,
+}
+
+impl fmt::Debug for SyzygyTablebases {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SyzygyTablebases")
+            .field("syzygy_path", &self.syzygy_path)
+            .field("max_pieces", &self.max_pieces())
+            .finish()
+    }
+}
+
+impl SyzygyTablebases {
+    pub fn new() -> SyzygyTablebases {
+        SyzygyTablebases {
+            syzygy_path: String::new(),
+            tablebase: None,
+        }
+    }
+
+    /// Loads the tablebases of the options, if the path was changed since the last search.
+    pub fn configure(&mut self, options: &Options) {
+        if options.syzygy_path() == self.syzygy_path {
+            return;
+        }
+        self.syzygy_path = options.syzygy_path().to_string();
+        self.tablebase = None;
+
+        if self.syzygy_path.is_empty() {
+            info!("No syzygy tablebases configured");
+            return;
+        }
+
+        let mut tablebase = Tablebase::new();
+        let mut files = 0;
+        for directory in env::split_paths(&self.syzygy_path) {
+            match tablebase.add_directory(&directory) {
+                Ok(added) => files += added,
+                Err(error) => {
+                    warn!("The syzygy directory [{}] could not be read: {}", directory.display(), error);
+                    println!("info string the syzygy directory {} could not be read: {}", directory.display(), error);
+                }
+            }
+        }
+
+        if files == 0 {
+            warn!("No syzygy tablebases found in [{}]", self.syzygy_path);
+            return;
+        }
+        info!("Loaded [{}] syzygy tables for up to [{}] pieces from [{}]", files, tablebase.max_pieces(), self.syzygy_path);
+        println!("info string found {} syzygy tables for up to {} pieces", files, tablebase.max_pieces());
+        self.tablebase = Some(tablebase);
+    }
+
+    /// Returns the maximum number of pieces of the loaded tables, 0 without tablebases.
+    pub fn max_pieces(&self) -> usize {
+        self.tablebase.as_ref().map_or(0, |tablebase| tablebase.max_pieces())
+    }
+
+    /// Returns whether the position on the board may be found in the tables.
+    /// The tables contain no positions with castling rights.
+    pub fn covers(&self, board: &Board) -> bool {
+        self.tablebase.is_some()
+            && board.piece_count() as usize <= self.max_pieces()
+            && !board.white_can_castle_short()
+            && !board.white_can_castle_long()
+            && !board.black_can_castle_short()
+            && !board.black_can_castle_long()
+    }
+
+    /// Returns the result of the position for the player to move, if the tables contain it.
+    pub fn probe_wdl(&self, board: &Board) -> Option<Wdl> {
+        let tablebase = self.tablebase.as_ref()?;
+        if !self.covers(board) {
+            return None;
+        }
+        let position = to_position(board)?;
+        tablebase.probe_wdl_after_zeroing(&position).ok()
+    }
+
+    /// Returns the legal moves ranked by their result, then by the distance to zeroing, best move first.
+    /// Returns None if any position is missing in the tables.
+    pub fn rank_moves(&self, board: &Board) -> Option<Vec<TablebaseMove>> {
+        let tablebase = self.tablebase.as_ref()?;
+        if !self.covers(board) {
+            return None;
+        }
+
+        let legal_moves = PieceRule::get_legal_moves(board, &board.next_color());
+        let mut ranked_moves = Vec::with_capacity(legal_moves.len());
+        for chess_move in legal_moves {
+            let zeroing = board.get_piece(chess_move.to_field()).is_some()
+                || board.get_piece(chess_move.from_field()).is_some_and(|piece| piece.piece_type() == PieceType::PAWN);
+
+            let mut new_board = board.clone();
+            new_board.play_move(&chess_move);
+            let position = to_position(&new_board)?;
+
+            let wdl = match tablebase.probe_wdl_after_zeroing(&position) {
+                Ok(wdl) => wdl,
+                Err(error) => {
+                    debug!("Syzygy probe failed: {}", error);
+                    return None;
+                }
+            };
+            let dtz = match tablebase.probe_dtz(&position) {
+                Ok(dtz) => dtz.ignore_rounding().0,
+                Err(error) => {
+                    debug!("Syzygy probe failed: {}", error);
+                    return None;
+                }
+            };
+
+            ranked_moves.push(TablebaseMove {
+                chess_move,
+                wdl: respect_fifty_move_rule(-wdl, dtz, new_board.halfmove_clock()),
+                dtz,
+                zeroing,
+                checkmate: position.is_checkmate(),
+            });
+        }
+
+        sort_ranked_moves(&mut ranked_moves);
+        Some(ranked_moves)
+    }
+}
+
+/// Sorts the moves by their result, then by the distance to zeroing, best move first.
+fn sort_ranked_moves(ranked_moves: &mut [TablebaseMove]) {
+    // the opponent loses faster the closer his negative distance is to zero, and
+    // saves his loss longer the higher his positive distance is. a won zeroing move
+    // resets the 50-move rule, so it is preferred over a shorter distance.
+    ranked_moves.sort_by_key(|ranked_move| Reverse((
+        ranked_move.wdl,
+        ranked_move.checkmate,
+        ranked_move.zeroing && ranked_move.wdl == Wdl::Win,
+        ranked_move.dtz,
+    )));
+}
+
+/// Turns a win into a cursed win and a loss into a blessed loss, if the 50-move rule
+/// ends the game before the next zeroing move. The distance is counted from the position
+/// after the move with the given halfmove clock.
+fn respect_fifty_move_rule(wdl: Wdl, dtz: i32, halfmove_clock: u16) -> Wdl {
+    let is_spoiled = dtz.unsigned_abs() + halfmove_clock as u32 > 100;
+    match wdl {
+        Wdl::Win if is_spoiled => Wdl::CursedWin,
+        Wdl::Loss if is_spoiled => Wdl::BlessedLoss,
+        wdl => wdl,
+    }
+}
+
+/// Converts the board into a position of shakmaty, which the tables are probed with.
+fn to_position(board: &Board) -> Option<Chess> {
+    let mut setup = Setup::empty();
+
+    for rank in 0..8 {
+        for file in 0..8 {
+            let field = Field::new(File::from_index(file), Rank::from_index(rank));
+            if let Some(piece) = board.get_piece(&field) {
+                let role = match piece.piece_type() {
+                    PieceType::PAWN => Role::Pawn,
+                    PieceType::KNIGHT => Role::Knight,
+                    PieceType::BISHOP => Role::Bishop,
+                    PieceType::ROOK => Role::Rook,
+                    PieceType::QUEEN => Role::Queen,
+                    PieceType::KING => Role::King,
+                };
+                let color = if piece.color() == Color::WHITE { shakmaty::Color::White } else { shakmaty::Color::Black };
+                setup.board.set_piece_at(Square::new((rank * 8 + file) as u32), role.of(color));
+            }
+        }
+    }
+
+    setup.turn = if board.next_color() == Color::WHITE { shakmaty::Color::White } else { shakmaty::Color::Black };
+    setup.castling_rights = Bitboard::EMPTY;
+    setup.ep_square = board.en_passant_field()
+        .map(|field| Square::new((field.rank().to_index() * 8 + field.file().to_index()) as u32));
+    setup.halfmoves = board.halfmove_clock() as u32;
+    setup.fullmoves = NonZeroU32::new(board.move_counter() as u32).unwrap_or(NonZeroU32::MIN);
+
+    // the board keeps en passant fields that no pawn can capture on
+    match Chess::from_setup(setup, CastlingMode::Standard) {
+        Ok(position) => Some(position),
+        Err(error) => error.ignore_invalid_ep_square().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::converter::Converter;
+
+    fn tablebase_move(uci: &str, wdl: Wdl, dtz: i32, zeroing: bool, checkmate: bool) -> TablebaseMove {
+        TablebaseMove { chess_move: Converter::convert_string_to_move(&uci.to_string()), wdl, dtz, zeroing, checkmate }
+    }
+
+    fn sorted_ucis(mut moves: Vec<TablebaseMove>) -> Vec<String> {
+        sort_ranked_moves(&mut moves);
+        moves.iter().map(|ranked_move| Converter::convert_move_to_string(&ranked_move.chess_move)).collect()
+    }
+
+    #[test]
+    fn fifty_move_rule_spoils_results_beyond_the_halfmove_clock() {
+        let cases = [
+            // result, distance to zeroing of the opponent, halfmove clock after the move, expected result
+            (Wdl::Win, -10, 0, Wdl::Win),
+            (Wdl::Win, -60, 40, Wdl::Win),
+            (Wdl::Win, -61, 40, Wdl::CursedWin),
+            (Wdl::Win, -101, 0, Wdl::CursedWin),
+            (Wdl::Win, -1, 99, Wdl::Win),
+            (Wdl::Win, -2, 99, Wdl::CursedWin),
+            (Wdl::Loss, 30, 70, Wdl::Loss),
+            (Wdl::Loss, 30, 71, Wdl::BlessedLoss),
+            (Wdl::Loss, 101, 0, Wdl::BlessedLoss),
+            (Wdl::Draw, 0, 100, Wdl::Draw),
+            (Wdl::CursedWin, -120, 0, Wdl::CursedWin),
+            (Wdl::CursedWin, -20, 0, Wdl::CursedWin),
+            (Wdl::BlessedLoss, 120, 90, Wdl::BlessedLoss),
+        ];
+        for (wdl, dtz, halfmove_clock, expected) in cases {
+            assert_eq!(respect_fifty_move_rule(wdl, dtz, halfmove_clock), expected, "{:?} {} {}", wdl, dtz, halfmove_clock);
+        }
+    }
+
+    #[test]
+    fn moves_are_ranked_by_result() {
+        let moves = vec![
+            tablebase_move("a1a2", Wdl::Loss, 12, false, false),
+            tablebase_move("b1b2", Wdl::Draw, 0, false, false),
+            tablebase_move("c1c2", Wdl::BlessedLoss, 110, false, false),
+            tablebase_move("d1d2", Wdl::Win, -15, false, false),
+            tablebase_move("e1e2", Wdl::CursedWin, -105, false, false),
+        ];
+        assert_eq!(sorted_ucis(moves), vec!["d1d2", "e1e2", "b1b2", "c1c2", "a1a2"]);
+    }
+
+    #[test]
+    fn wins_are_ranked_by_checkmate_zeroing_and_distance() {
+        let moves = vec![
+            tablebase_move("a1a2", Wdl::Win, -20, false, false),
+            tablebase_move("b1b2", Wdl::Win, -5, false, false),
+            tablebase_move("c2c4", Wdl::Win, -30, true, false),
+            tablebase_move("h7h8", Wdl::Win, 0, true, true),
+        ];
+        assert_eq!(sorted_ucis(moves), vec!["h7h8", "c2c4", "b1b2", "a1a2"]);
+    }
+
+    #[test]
+    fn losses_last_as_long_as_possible() {
+        let moves = vec![
+            tablebase_move("a1a2", Wdl::Loss, 3, false, false),
+            tablebase_move("b2b3", Wdl::Loss, 1, true, false),
+            tablebase_move("c1c2", Wdl::Loss, 40, false, false),
+        ];
+        // zeroing only counts for wins
+        assert_eq!(sorted_ucis(moves), vec!["c1c2", "a1a2", "b2b3"]);
+    }
+}
@@ -348,7 +348,7 @@ fn get_legal_moves_pawn(board: &Board, field: &Field) -> Vec<ChessMove> {
             || field.rank() == Rank::SEVEN && color == Color::BLACK {
         let double_forward = Field::new(
             File::from_index(field_file),
-            Rank::from_index(field_rank + 2*direction as usize)
+            Rank::from_index((field_rank as i8 + 2 * direction) as usize)
         );
         if board.get_piece(&forward).is_none() && board.get_piece(&double_forward).is_none() {
             moves.push(ChessMove::new(field.clone(), double_forward, promotion_type));
@@ -486,6 +486,23 @@ fn is_king_in_range(board: &Board, field: &Field, color: Color) -> bool {
 
 /// Returns if the given field is covered by a piece of the given color.
 fn is_field_covered(board: &Board, field: &Field, color: Color) -> bool {
+    visit_covering_pieces(board, field, color, &mut |_| true)
+}
+
+/// Returns the types of all pieces of the given color that cover the given field.
+/// A type is contained once for every piece of that type.
+pub fn get_covering_pieces(board: &Board, field: &Field, color: Color) -> Vec<PieceType> {
+    let mut covering_pieces = Vec::new();
+    visit_covering_pieces(board, field, color, &mut |piece_type| {
+        covering_pieces.push(piece_type);
+        false
+    });
+    covering_pieces
+}
+
+/// Calls visit with the type of every piece of the given color that covers the given field,
+/// until visit returns true. Returns whether visit returned true.
+fn visit_covering_pieces(board: &Board, field: &Field, color: Color, visit: &mut impl FnMut(PieceType) -> bool) -> bool {
     trace!("Checking if {:?} is covered by {:?}", field, color);
     let pawn_direction: isize = match color {
         Color::WHITE => -1,
@@ -516,13 +533,15 @@ fn is_field_covered(board: &Board, field: &Field, color: Color) -> bool {
             None
         };
 
-        if diagonal_left.is_some_and(|d| board.get_piece(&d).is_some_and(|p|
-                p.color() == color && p.piece_type() == PieceType::PAWN))
-            || diagonal_right.is_some_and(|d| board.get_piece(&d).is_some_and(|p|
-                p.color() == color && p.piece_type() == PieceType::PAWN))
-        {
-            trace!("Field is covered by a pawn");
-            return true;
+        for diagonal in [diagonal_left, diagonal_right].iter().flatten() {
+            if board.get_piece(diagonal).is_some_and(|p|
+                p.color() == color && p.piece_type() == PieceType::PAWN)
+            {
+                trace!("Field is covered by a pawn");
+                if visit(PieceType::PAWN) {
+                    return true;
+                }
+            }
         }
     }
 
@@ -539,35 +558,41 @@ fn is_field_covered(board: &Board, field: &Field, color: Color) -> bool {
                 piece.color() == color && piece.piece_type() == PieceType::KNIGHT)
             {
                 trace!("Field is covered by a knight");
-                return true;
+                if visit(PieceType::KNIGHT) {
+                    return true;
+                }
             }
         }
     }
 
     // field is covered by rook or queen
-    if get_visible_fields_along_direction(board, field, ROOK_DIRECTIONS.to_vec()).iter()
+    for piece in get_visible_fields_along_direction(board, field, ROOK_DIRECTIONS.to_vec()).iter()
         .filter_map(|f|
             *board.get_piece(f)
-        ).any(|p|
+        ).filter(|p|
             p.color() == color &&
                 (p.piece_type() == PieceType::QUEEN || p.piece_type() == PieceType::ROOK)
         )
     {
         trace!("Field is covered by a rook or a queen");
-        return true;
+        if visit(piece.piece_type()) {
+            return true;
+        }
     }
 
     // field is covered by bishop or queen
-    if get_visible_fields_along_direction(board, field, BISHOP_DIRECTIONS.to_vec()).iter()
+    for piece in get_visible_fields_along_direction(board, field, BISHOP_DIRECTIONS.to_vec()).iter()
         .filter_map(|f|
             *board.get_piece(f)
-        ).any(|p|
+        ).filter(|p|
         p.color() == color &&
             (p.piece_type() == PieceType::QUEEN || p.piece_type() == PieceType::BISHOP)
     )
     {
         trace!("Field is covered by a bishop or a queen");
-        return true;
+        if visit(piece.piece_type()) {
+            return true;
+        }
     }
 
     // field is covered by king
@@ -588,7 +613,9 @@ fn is_field_covered(board: &Board, field: &Field, color: Color) -> bool {
                 piece.color() == color && piece.piece_type() == PieceType::KING)
             {
                 trace!("Field is covered by a king");
-                return true;
+                if visit(PieceType::KING) {
+                    return true;
+                }
             }
         }
     }
@@ -0,0 +1,49 @@
+use log::warn;
+use crate::evaluation::evaluation_weights::EvaluationWeights;
+use crate::tuning::texel_tuner::TexelTuner;
+
+pub mod texel_tuner;
+
+/// Number of passes over all weights, if the command line does not give one
+const DEFAULT_PASSES: u32 = 1000;
+
+/// Runs the tune command of the command line:
+/// tune <positions file> <output file> [passes] [start weights file]
+///
+/// Without start weights the tuning starts from the built in weights.
+pub fn run(arguments: &[String]) {
+    let (Some(positions_path), Some(output_path)) = (arguments.first(), arguments.get(1)) else {
+        println!("Usage: tune <positions file> <output file> [passes] [start weights file]");
+        return;
+    };
+    let passes = arguments.get(2).and_then(|passes| passes.parse::<u32>().ok()).unwrap_or(DEFAULT_PASSES);
+
+    let start_weights = match arguments.get(3) {
+        Some(path) => match EvaluationWeights::load(path) {
+            Ok(weights) => weights,
+            Err(error) => {
+                warn!("Could not load start weights [{}]: {}", path, error);
+                println!("Could not load start weights {}: {}", path, error);
+                return;
+            }
+        },
+        None => EvaluationWeights::default(),
+    };
+
+    let positions = match TexelTuner::read_positions(positions_path) {
+        Ok(positions) if !positions.is_empty() => positions,
+        Ok(_) => {
+            println!("No labelled positions found in {}", positions_path);
+            return;
+        }
+        Err(error) => {
+            println!("Could not read positions {}: {}", positions_path, error);
+            return;
+        }
+    };
+
+    match TexelTuner::new(positions).tune(start_weights, output_path, passes) {
+        Ok(_) => println!("Wrote tuned weights to {}", output_path),
+        Err(error) => println!("Could not write tuned weights {}: {}", output_path, error),
+    }
+}
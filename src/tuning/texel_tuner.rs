@@ -0,0 +1,301 @@
+use std::fs;
+use std::io;
+use log::{info, warn};
+use rayon::prelude::*;
+use serde_json::Value;
+use crate::converter::converter::Converter;
+use crate::datamodel::board::Board;
+use crate::datamodel::enums::color::Color;
+use crate::evaluation::classical_evaluator::ClassicalEvaluator;
+use crate::evaluation::evaluation_weights::EvaluationWeights;
+use crate::evaluation::Evaluator;
+
+/// Range and step of the scaling constant that is fitted before tuning
+const SCALING_MIN: f64 = 0.2;
+const SCALING_MAX: f64 = 3.0;
+const SCALING_STEP: f64 = 0.02;
+
+/// A position of a played game and the result of the game from the view of white,
+/// 1 for a win, 0.5 for a draw and 0 for a loss.
+pub struct LabelledPosition {
+    board: Board,
+    result: f64,
+}
+
+/// Tunes the weights of the classical evaluator with the method of Peter Österlund (Texel).
+///
+/// The evaluation of every position is mapped to an expected game result by a logistic function.
+/// The tuner minimizes the mean squared error between the expected and the real results
+/// by a local search, that changes one weight at a time by one centipawn as long as the error shrinks.
+/// Every weight takes part. The values of the pieces are the values of the piece square weights,
+/// which the material evaluator and the move ordering use as well.
+/// The positions should be quiet, as the evaluation does not resolve captures.
+pub struct TexelTuner {
+    positions: Vec<LabelledPosition>,
+    /// Scales the evaluation before it is mapped to a result, so the error of the start weights is minimal
+    scaling: f64,
+}
+
+impl TexelTuner {
+    pub fn new(positions: Vec<LabelledPosition>) -> TexelTuner {
+        TexelTuner {
+            positions,
+            scaling: 1.0,
+        }
+    }
+
+    /// Reads positions in fen or epd notation, each followed by the game result on the same line.
+    /// The result may be given as 1-0, 0-1 or 1/2-1/2, also within quotes like c9 "1-0";
+    /// or as [1.0], [0.5] or [0.0]. Lines without result are skipped.
+    pub fn read_positions(path: &str) -> io::Result<Vec<LabelledPosition>> {
+        let content = fs::read_to_string(path)?;
+        let positions: Vec<LabelledPosition> = content.lines().filter_map(parse_position).collect();
+        info!("Read [{}] labelled positions from [{}]", positions.len(), path);
+        Ok(positions)
+    }
+
+    /// Tunes the start weights until no single change improves them or the number of passes is reached.
+    /// The weights are written to the output file after every pass, so an interrupted run keeps its progress.
+    pub fn tune(&mut self, start_weights: EvaluationWeights, output_path: &str, max_passes: u32) -> io::Result<EvaluationWeights> {
+        self.scaling = self.fit_scaling(&start_weights);
+        println!("Fitted scaling constant [{:.2}]", self.scaling);
+
+        let template = serde_json::to_value(&start_weights).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        let mut named_parameters = Vec::new();
+        collect_parameters(&template, "", &mut named_parameters);
+        let tuned_indices: Vec<usize> = named_parameters.iter().enumerate()
+            .filter(|(_, (name, _))| affects_evaluation(name))
+            .map(|(index, _)| index)
+            .collect();
+        let mut parameters: Vec<i32> = named_parameters.into_iter().map(|(_, parameter)| parameter).collect();
+        let mut best_error = self.error(&start_weights);
+        let mut best_weights = start_weights;
+        println!("Tuning [{}] parameters on [{}] positions, start error [{:.6}]", tuned_indices.len(), self.positions.len(), best_error);
+
+        for pass in 1..=max_passes {
+            let mut improved = false;
+
+            for &index in &tuned_indices {
+                for step in [1, -1] {
+                    parameters[index] += step;
+                    let weights = weights_from_parameters(&template, &parameters);
+                    let error = self.error(&weights);
+
+                    if error < best_error {
+                        best_error = error;
+                        best_weights = weights;
+                        improved = true;
+                        break;
+                    }
+                    parameters[index] -= step;
+                }
+            }
+
+            best_weights.save(output_path)?;
+            println!("Finished pass [{}], error [{:.6}]", pass, best_error);
+            info!("Finished tuning pass [{}] with error [{}]", pass, best_error);
+
+            if !improved {
+                break;
+            }
+        }
+
+        Ok(best_weights)
+    }
+
+    /// Returns the scaling constant that minimizes the error of the given weights.
+    fn fit_scaling(&self, weights: &EvaluationWeights) -> f64 {
+        let evaluations = self.white_evaluations(weights);
+        let mut best = (f64::MAX, 1.0);
+
+        let mut scaling = SCALING_MIN;
+        while scaling <= SCALING_MAX {
+            let error = mean_squared_error(&self.positions, &evaluations, scaling);
+            if error < best.0 {
+                best = (error, scaling);
+            }
+            scaling += SCALING_STEP;
+        }
+        best.1
+    }
+
+    fn error(&self, weights: &EvaluationWeights) -> f64 {
+        mean_squared_error(&self.positions, &self.white_evaluations(weights), self.scaling)
+    }
+
+    /// Evaluates all positions from the view of white.
+    fn white_evaluations(&self, weights: &EvaluationWeights) -> Vec<i32> {
+        let evaluator = ClassicalEvaluator::without_pawn_hash_table(weights.clone());
+        self.positions.par_iter()
+            .map(|position| {
+                let evaluation = evaluator.evaluate(&position.board);
+                if position.board.next_color() == Color::WHITE { evaluation } else { -evaluation }
+            })
+            .collect()
+    }
+}
+
+/// Maps an evaluation in centipawns to the expected result from the view of white.
+fn expected_result(evaluation: i32, scaling: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-scaling * evaluation as f64 / 400.0))
+}
+
+fn mean_squared_error(positions: &[LabelledPosition], evaluations: &[i32], scaling: f64) -> f64 {
+    let sum: f64 = positions.iter().zip(evaluations)
+        .map(|(position, evaluation)| (position.result - expected_result(*evaluation, scaling)).powi(2))
+        .sum();
+    sum / positions.len().max(1) as f64
+}
+
+fn parse_position(line: &str) -> Option<LabelledPosition> {
+    let result = if line.contains("1/2-1/2") || line.contains("[0.5]") {
+        0.5
+    } else if line.contains("1-0") || line.contains("[1.0]") {
+        1.0
+    } else if line.contains("0-1") || line.contains("[0.0]") {
+        0.0
+    } else {
+        return None;
+    };
+
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < 4 {
+        return None;
+    }
+    // epd has no move counters, the board needs them nevertheless
+    let has_counters = fields.len() >= 6 && fields[4].parse::<u16>().is_ok() && fields[5].parse::<u16>().is_ok();
+    let fen = if has_counters {
+        fields[..6].join(" ")
+    } else {
+        format!("{} 0 1", fields[..4].join(" "))
+    };
+
+    if let Err(error) = Converter::validate_fen(&fen) {
+        warn!("Skipping the position [{}]: {}", line, error);
+        return None;
+    }
+
+    Some(LabelledPosition {
+        board: Converter::convert_string_to_board(fen),
+        result,
+    })
+}
+
+/// Appends all numbers of the serialized weights with their names in a fixed order.
+/// The name is the path of keys and indices, e.g. piece_square.pawn.midgame_table.1.3
+fn collect_parameters(value: &Value, name: &str, parameters: &mut Vec<(String, i32)>) {
+    let child_name = |key: &str| if name.is_empty() { key.to_string() } else { format!("{}.{}", name, key) };
+    match value {
+        Value::Number(number) => parameters.push((name.to_string(), number.as_i64().unwrap_or(0) as i32)),
+        Value::Array(values) => values.iter().enumerate()
+            .for_each(|(index, value)| collect_parameters(value, &child_name(&index.to_string()), parameters)),
+        Value::Object(map) => map.iter()
+            .for_each(|(key, value)| collect_parameters(value, &child_name(key), parameters)),
+        _ => (),
+    }
+}
+
+/// Returns whether the parameter of the given name may change the evaluation. Both players
+/// always have their king, so its value cancels out, and pawns never stand on the first or last rank.
+fn affects_evaluation(name: &str) -> bool {
+    if name.starts_with("piece_square.king.value.") {
+        return false;
+    }
+    let pawn_table_row = name.strip_prefix("piece_square.pawn.midgame_table.")
+        .or_else(|| name.strip_prefix("piece_square.pawn.endgame_table."))
+        .and_then(|field| field.split('.').next());
+    !matches!(pawn_table_row, Some("0") | Some("7"))
+}
+
+/// Replaces the numbers of the serialized weights in the order of collect_parameters.
+fn apply_parameters(value: &mut Value, parameters: &mut impl Iterator<Item = i32>) {
+    match value {
+        Value::Number(number) => {
+            if let Some(parameter) = parameters.next() {
+                *number = parameter.into();
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(|value| apply_parameters(value, parameters)),
+        Value::Object(map) => map.values_mut().for_each(|value| apply_parameters(value, parameters)),
+        _ => (),
+    }
+}
+
+fn weights_from_parameters(template: &Value, parameters: &[i32]) -> EvaluationWeights {
+    let mut value = template.clone();
+    apply_parameters(&mut value, &mut parameters.iter().copied());
+    serde_json::from_value(value).expect("Parameters have the layout of the weights")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datamodel::enums::color::Color;
+
+    #[test]
+    fn results_are_read_in_all_notations() {
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+        for (label, result) in [("1/2-1/2", 0.5), ("[0.5]", 0.5), ("1-0", 1.0), ("[1.0]", 1.0), ("0-1", 0.0), ("[0.0]", 0.0)] {
+            let position = parse_position(&format!("{} {}", fen, label)).expect(label);
+            assert_eq!(position.result, result, "{}", label);
+        }
+        assert!(parse_position(&format!("{} \"1/2-1/2\";", fen)).is_some_and(|position| position.result == 0.5));
+    }
+
+    #[test]
+    fn move_counters_are_optional() {
+        let position = parse_position("4k3/8/8/8/8/8/4P3/4K3 b - - 7 42 [1.0]").unwrap();
+        assert!(position.board.next_color() == Color::BLACK);
+        assert_eq!(position.board.halfmove_clock(), 7);
+        assert_eq!(position.board.move_counter(), 42);
+
+        let position = parse_position("4k3/8/8/8/8/8/4P3/4K3 w - - c9 \"1-0\";").unwrap();
+        assert!(position.board.next_color() == Color::WHITE);
+        assert_eq!(position.board.halfmove_clock(), 0);
+        assert_eq!(position.board.move_counter(), 1);
+    }
+
+    #[test]
+    fn invalid_lines_are_skipped() {
+        for line in [
+            "",
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/4P3/4K3 w [0.5]",
+            "4k3/8/8/8/8/4P3/4K3 w - - 0 1 [0.5]",
+            "4k3/8/8/8/8/8/4P3/4K4 w - - 0 1 [0.5]",
+            "4k3/8/8/8/8/8/4X3/4K3 w - - 0 1 [0.5]",
+            "8/8/8/8/8/8/4P3/4K3 w - - 0 1 [0.5]",
+            "4k3/8/8/8/8/8/4P3/4K3 x - - 0 1 [0.5]",
+            "4k3/8/8/8/8/8/4P3/4K3 w X - 0 1 [0.5]",
+            "4k3/8/8/8/8/8/4P3/4K3 w - e9 0 1 [0.5]",
+        ] {
+            assert!(parse_position(line).is_none(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn parameters_without_effect_are_skipped() {
+        assert!(!affects_evaluation("piece_square.king.value.midgame"));
+        assert!(!affects_evaluation("piece_square.king.value.endgame"));
+        assert!(!affects_evaluation("piece_square.pawn.midgame_table.0.3"));
+        assert!(!affects_evaluation("piece_square.pawn.endgame_table.7.0"));
+        assert!(affects_evaluation("piece_square.pawn.midgame_table.1.3"));
+        assert!(affects_evaluation("piece_square.pawn.value.midgame"));
+        assert!(affects_evaluation("piece_square.king.midgame_table.0.6"));
+        assert!(affects_evaluation("piece_square.knight.endgame_table.7.1"));
+    }
+
+    #[test]
+    fn parameters_are_applied_in_the_order_they_are_collected() {
+        let template = serde_json::to_value(EvaluationWeights::default()).unwrap();
+        let mut parameters = Vec::new();
+        collect_parameters(&template, "", &mut parameters);
+        assert!(parameters.iter().any(|(name, _)| name == "piece_square.pawn.midgame_table.1.3"));
+
+        let values: Vec<i32> = parameters.iter().map(|(_, value)| value + 1).collect();
+        let weights = weights_from_parameters(&template, &values);
+        let mut changed_parameters = Vec::new();
+        collect_parameters(&serde_json::to_value(weights).unwrap(), "", &mut changed_parameters);
+        assert_eq!(changed_parameters.into_iter().map(|(_, value)| value).collect::<Vec<i32>>(), values);
+    }
+}